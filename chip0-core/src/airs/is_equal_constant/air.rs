@@ -24,5 +24,6 @@ impl<AB: AirBuilder> Air<AB> for IsEqualConstantAir {
             AB::Expr::one() - diff.clone() * local.diff_inv,
             local.is_equal,
         );
+        builder.assert_zero(diff * local.is_equal);
     }
 }
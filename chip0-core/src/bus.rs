@@ -9,5 +9,7 @@ pub enum Chip0MachineBus {
     FrameBufferBus = 4,
     RangeBus = 5,
     MemoryStartBus = 6,
-    // HashBus = 7,
+    HashBus = 7,
+    RandomBus = 8,
+    MemoryFinalBus = 9,
 }
@@ -22,6 +22,7 @@ impl<F: Field> BaseInteractionAir<F> for ClearChip {
                     VirtualPairCol::single_main(col_map.addr),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::constant(F::zero()),
+                    VirtualPairCol::constant(F::zero()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_real),
                 argument_index: self.bus_frame_buffer,
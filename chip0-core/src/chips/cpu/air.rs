@@ -1,16 +1,24 @@
-use chip8_core::constants::{NUM_KEYS, NUM_OPCODES, NUM_REGISTERS, OPCODE_SIZE};
+use chip8_core::{
+    constants::{
+        FLAG_REGISTER, FONTSET_START_ADDRESS, FONT_SIZE, NUM_KEYS, NUM_OPCODES, NUM_REGISTERS,
+        OPCODE_SIZE, STACK_DEPTH, TICKS_PER_TIMER,
+    },
+    quirks::Quirks,
+};
 use core::borrow::Borrow;
 use itertools::Itertools;
-use p3_air::{Air, AirBuilder, BaseAir};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
 use p3_air_util::builders::SubAirBuilder;
 use p3_field::AbstractField;
 use p3_matrix::Matrix;
 
-use crate::airs::counter::CounterAir;
 use crate::airs::is_equal::IsEqualAir;
+use crate::airs::modulo_counter::ModuloCounterAir;
 use crate::airs::selector::SelectorAir;
+use crate::chips::draw::columns::WORD_BITS;
+use crate::public_values::PublicValues;
 
-use super::columns::CpuCols;
+use super::columns::{CpuCols, NIBBLE_BITS};
 use super::CpuChip;
 
 impl<F> BaseAir<F> for CpuChip {
@@ -19,7 +27,7 @@ impl<F> BaseAir<F> for CpuChip {
     }
 }
 
-impl<AB: AirBuilder> Air<AB> for CpuChip {
+impl<AB: AirBuilderWithPublicValues> Air<AB> for CpuChip {
     fn eval(&self, builder: &mut AB) {
         let main = builder.main();
         let local = main.row_slice(0);
@@ -29,16 +37,70 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
 
         let col_map = CpuCols::<AB::Var>::col_map();
 
+        let public_values = builder.public_values().to_vec();
+        let public_values: &PublicValues<AB::PublicVar> = public_values.as_slice().borrow();
+
         // is_real is boolean
         builder.assert_bool(local.is_real);
 
+        // Real rows are followed by exactly one final row holding the state after execution
+        builder.assert_bool(local.is_final);
+        builder.when(local.is_final).assert_zero(local.is_real);
+        builder
+            .when_first_row()
+            .assert_one(local.is_real + local.is_final);
+        builder
+            .when_transition()
+            .when(local.is_real)
+            .assert_one(next.is_real + next.is_final);
+        builder
+            .when_transition()
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(next.is_real + next.is_final);
+        builder.when_last_row().assert_zero(local.is_real);
+
+        // Segment starts from the state in the public values
+        builder
+            .when_first_row()
+            .assert_eq(local.clk, public_values.start_clk);
+        builder
+            .when_first_row()
+            .assert_eq(local.program_counter, public_values.start_program_counter);
+
         // clk
-        // TODO: See if can avoid is_real
-        let counter = CounterAir;
-        let mut builder_when_next_is_real = builder.when(next.is_real);
-        let mut clk_builder =
-            SubAirBuilder::new_main(&mut builder_when_next_is_real, vec![col_map.clk]);
-        counter.eval(&mut clk_builder);
+        builder
+            .when_transition()
+            .when(next.is_real + next.is_final)
+            .assert_eq(next.clk, local.clk + AB::Expr::one());
+
+        // Final row carries over the state and exposes it as the end state
+        let mut builder_when_next_is_final = builder.when_transition();
+        let mut builder_when_next_is_final = builder_when_next_is_final.when(next.is_final);
+        for i in 0..NUM_KEYS {
+            builder_when_next_is_final.assert_eq(next.keypad[i], local.keypad[i]);
+        }
+
+        let mut builder_when_local_is_final = builder.when(local.is_final);
+        builder_when_local_is_final.assert_eq(local.clk, public_values.end_clk);
+        builder_when_local_is_final
+            .assert_eq(local.program_counter, public_values.end_program_counter);
+        builder_when_local_is_final
+            .assert_eq(local.index_register, public_values.end_index_register);
+        builder_when_local_is_final.assert_eq(local.stack_pointer, public_values.end_stack_pointer);
+        builder_when_local_is_final.assert_eq(local.delay_timer, public_values.end_delay_timer);
+        builder_when_local_is_final.assert_eq(local.sound_timer, public_values.end_sound_timer);
+        for i in 0..NUM_REGISTERS {
+            builder_when_local_is_final
+                .assert_eq(local.registers[i], public_values.end_registers[i]);
+        }
+        for i in 0..STACK_DEPTH {
+            builder_when_local_is_final.assert_eq(local.stack[i], public_values.end_stack[i]);
+        }
+        for i in 0..NUM_KEYS {
+            builder_when_local_is_final.assert_eq(local.keypad[i], public_values.end_keypad[i]);
+        }
+        builder_when_local_is_final.assert_eq(local.random_state, public_values.end_random_state);
+        builder_when_local_is_final.assert_eq(local.wait_key, public_values.end_wait_key);
 
         // Opcode selectors
         let selector = SelectorAir::<NUM_OPCODES>;
@@ -84,12 +146,46 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
         );
         selector.eval(&mut selector_builder);
 
+        // Decoding. The opcode splits into nibbles and each selector fixes the bits of its opcode
+        // pattern. The x and y nibbles are bounded by their selectors and n by the range bus.
+        let nibble_base = AB::Expr::from_canonical_u32(1 << NIBBLE_BITS);
+        let byte_base = AB::Expr::from_canonical_u32(1 << WORD_BITS);
+        let mut builder_when_local_is_real = builder.when(local.is_real);
+        builder_when_local_is_real.assert_eq(
+            local.opcode_hi,
+            local.opcode_top * nibble_base.clone() + local.x,
+        );
+        builder_when_local_is_real
+            .assert_eq(local.opcode_lo, local.y * nibble_base.clone() + local.n);
+        builder_when_local_is_real.assert_eq(
+            local.opcode,
+            local.opcode_hi * byte_base.clone() + local.opcode_lo,
+        );
+        builder_when_local_is_real.assert_eq(local.nn, local.opcode_lo);
+        builder_when_local_is_real.assert_eq(local.nnn, local.x * byte_base + local.opcode_lo);
+
+        let nibbles = [local.opcode_top, local.x, local.y, local.n];
+        for (sel, pattern, mask) in decode_table(local) {
+            let masked_opcode = nibbles
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| (mask >> (NIBBLE_BITS * (3 - i))) & 0xF == 0xF)
+                .map(|(i, &nibble)| {
+                    nibble * AB::Expr::from_canonical_u32(1 << (NIBBLE_BITS * (3 - i)))
+                })
+                .sum::<AB::Expr>();
+            builder
+                .when(sel)
+                .assert_eq(masked_opcode, AB::Expr::from_canonical_u16(pattern));
+            builder
+                .when_ne(local.is_real, AB::Expr::one())
+                .assert_zero(sel);
+        }
+
         // register selectors
         for i in 0..NUM_REGISTERS {
             builder.assert_bool(local.x_sel[i]);
             builder.assert_bool(local.y_sel[i]);
-            // TODO: Add more constraints
-            builder.assert_bool(local.lte_x_sel[i]);
         }
         builder
             .when(local.is_real)
@@ -116,24 +212,397 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
                 .sum::<AB::Expr>(),
         );
 
+        // FX55 and FX65 transfer the registers up to x
+        for i in 0..NUM_REGISTERS {
+            builder.assert_eq(
+                local.lte_x_sel[i],
+                (local.is_store_registers + local.is_load_memory)
+                    * local.x_sel[i..].iter().map(|&x| x.into()).sum::<AB::Expr>(),
+            );
+        }
+
+        // Instructions writing vx put the written value in alu_result. With the VF reset quirk the
+        // bitwise instructions clear VF afterwards like the other 8XYN flags.
+        let is_logic = local.is_or + local.is_and + local.is_xor;
+        let mut is_flag_last =
+            local.is_add_xy + local.is_sub_xy + local.is_sub_yx + local.is_shift_left;
+        if self.quirks.vf_reset {
+            is_flag_last += is_logic.clone();
+            builder.when(is_logic.clone()).assert_zero(local.alu_flag);
+        }
+        let is_flag = is_flag_last.clone() + local.is_shift_right;
+        let is_write_vx = local.is_load
+            + local.is_add
+            + local.is_move
+            + is_logic
+            + is_flag.clone()
+            + local.is_random
+            + local.is_load_delay
+            + local.is_wait_key_done;
+
+        // Write selectors
+        for i in 0..NUM_REGISTERS {
+            let mut write_sel =
+                local.x_sel[i] * is_write_vx.clone() + local.lte_x_sel[i] * local.is_load_memory;
+            if i == FLAG_REGISTER {
+                write_sel += (AB::Expr::one() - local.x_sel[i]) * is_flag.clone() + local.is_draw
+                    - local.is_draw_wait;
+            }
+            builder.assert_eq(local.write_sel[i], write_sel);
+        }
+
+        // Written values. 8XYN instructions overwrite VF with the flag afterwards, except for
+        // 8XY6 which writes the flag first so vx wins when x is VF.
+        for i in 0..NUM_REGISTERS {
+            let is_write_result = if i == FLAG_REGISTER {
+                is_write_vx.clone() - is_flag_last.clone()
+            } else {
+                is_write_vx.clone()
+            };
+            builder
+                .when(is_write_result)
+                .assert_zero(local.x_sel[i] * (local.registers[i] - local.alu_result));
+        }
+        builder
+            .when(is_flag_last)
+            .assert_eq(local.registers[FLAG_REGISTER], local.alu_flag);
+        builder.when(local.is_shift_right).assert_eq(
+            local.registers[FLAG_REGISTER],
+            local.alu_flag + local.x_sel[FLAG_REGISTER] * (local.alu_result - local.alu_flag),
+        );
+        builder
+            .when(local.is_load)
+            .assert_eq(local.alu_result, local.nn);
+        builder
+            .when(local.is_move)
+            .assert_eq(local.alu_result, local.vy);
+        // DXYN waits for the next timer tick with the display wait quirk, leaving VF alone
+        builder.assert_bool(local.is_timer_tick);
+        builder
+            .when_transition()
+            .when(next.is_real + next.is_final)
+            .assert_eq(next.is_timer_tick, local.is_timer_counter_max);
+        builder
+            .when_first_row()
+            .when(local.is_real)
+            .assert_one(local.is_timer_tick);
+        if self.quirks.display_wait {
+            builder.assert_eq(
+                local.is_draw_wait,
+                local.is_draw * (AB::Expr::one() - local.is_timer_tick),
+            );
+        } else {
+            builder.assert_zero(local.is_draw_wait);
+        }
+        let is_draw_done = local.is_draw - local.is_draw_wait;
+
+        // DXY0 draws nothing and clears VF
+        builder.assert_zero(local.n * (AB::Expr::one() - local.n * local.n_inv));
+        builder.assert_eq(
+            local.is_draw_nonempty,
+            is_draw_done.clone() * local.n * local.n_inv,
+        );
+        builder
+            .when(is_draw_done.clone() - local.is_draw_nonempty)
+            .assert_zero(local.registers[FLAG_REGISTER]);
+
+        // CXNN advances the LFSR, whose step and output are checked by the random chip
+        builder
+            .when_ne(local.is_random, AB::Expr::one())
+            .assert_eq(local.random_state, local.prev_random_state);
+        builder
+            .when_transition()
+            .when(next.is_real + next.is_final)
+            .assert_eq(next.prev_random_state, local.random_state);
+        builder
+            .when_first_row()
+            .assert_eq(local.prev_random_state, public_values.start_random_state);
+
+        // FX0A stalls until a key goes down and is released again, so keys held when the wait
+        // starts don't count. Key changes come from the keypad bus through keypad_diff.
+        builder.assert_bool(local.is_wait_key_held);
+        builder.assert_bool(local.is_wait_key_done);
+        for i in 0..NUM_KEYS {
+            builder.assert_bool(local.wait_key_sel[i]);
+        }
+        let wait_key_sel_sum = local
+            .wait_key_sel
+            .into_iter()
+            .map(|x| x.into())
+            .sum::<AB::Expr>();
+        let wait_key = local
+            .wait_key_sel
+            .into_iter()
+            .enumerate()
+            .map(|(i, sel)| AB::Expr::from_canonical_usize(i + 1) * sel)
+            .sum::<AB::Expr>();
+        builder.assert_bool(wait_key_sel_sum.clone());
+        builder
+            .when_ne(local.is_wait_key_press, AB::Expr::one())
+            .assert_zero(local.is_wait_key_held);
+        builder
+            .when_ne(local.is_wait_key_press, AB::Expr::one())
+            .assert_zero(wait_key_sel_sum.clone());
         builder.assert_eq(
-            local.vx,
-            local
-                .x_sel
-                .iter()
-                .zip_eq(local.registers.iter())
-                .map(|(&sel, &register)| sel * register)
+            local.is_wait_key_idle,
+            local.is_wait_key_press * (AB::Expr::one() - local.is_wait_key_held),
+        );
+
+        // Only FX0A may run while a key is held and it keeps the wait key otherwise
+        builder
+            .when(local.is_real)
+            .when_ne(local.is_wait_key_press, AB::Expr::one())
+            .assert_zero(local.prev_wait_key);
+        builder
+            .when_ne(local.is_wait_key_press, AB::Expr::one())
+            .assert_eq(local.wait_key, local.prev_wait_key);
+        builder
+            .when(local.is_wait_key_idle)
+            .assert_zero(local.prev_wait_key);
+        builder
+            .when(local.is_wait_key_held)
+            .assert_eq(local.prev_wait_key, wait_key.clone());
+        builder
+            .when(local.is_wait_key_held)
+            .assert_one(wait_key_sel_sum.clone());
+
+        // A held key is done once released
+        let is_wait_key_down = local
+            .wait_key_sel
+            .into_iter()
+            .zip_eq(local.keypad)
+            .map(|(sel, key)| sel * key)
+            .sum::<AB::Expr>();
+        builder.assert_eq(
+            local.is_wait_key_done,
+            local.is_wait_key_held * (AB::Expr::one() - is_wait_key_down),
+        );
+
+        // While idle, the lowest key that went down in this cycle is selected, if any. Twice the
+        // press indicator of a key is diff^2 + diff.
+        let double_presses = local.keypad_diff.map(|diff| diff * diff + diff);
+        for i in 0..NUM_KEYS {
+            builder
+                .when(local.is_wait_key_idle)
+                .assert_zero(local.wait_key_sel[i] * (AB::Expr::two() - double_presses[i].clone()));
+        }
+        builder.when(local.is_wait_key_idle).assert_zero(
+            (0..NUM_KEYS)
+                .map(|i| {
+                    local.wait_key_sel[i] * double_presses[..i].iter().cloned().sum::<AB::Expr>()
+                })
                 .sum::<AB::Expr>(),
         );
+        builder.when(local.is_wait_key_idle).assert_zero(
+            (AB::Expr::one() - wait_key_sel_sum) * double_presses.iter().cloned().sum::<AB::Expr>(),
+        );
+
+        builder.when(local.is_wait_key_press).assert_eq(
+            local.wait_key,
+            wait_key - local.is_wait_key_done * local.prev_wait_key,
+        );
+        builder
+            .when(local.is_wait_key_done)
+            .assert_eq(local.alu_result, local.prev_wait_key - AB::Expr::one());
+        builder
+            .when_transition()
+            .when(next.is_real + next.is_final)
+            .assert_eq(next.prev_wait_key, local.wait_key);
+        builder
+            .when_first_row()
+            .assert_eq(local.prev_wait_key, public_values.start_wait_key);
+
+        // Registers hold the state after the instruction, so operands are read from the previous
+        // row or from the start state
+        let prev_registers: [AB::Expr; NUM_REGISTERS] = local.registers.map(Into::into);
+        let mut builder_when_next_is_row = builder.when_transition();
+        let mut builder_when_next_is_row =
+            builder_when_next_is_row.when(next.is_real + next.is_final);
+        eval_registers(
+            &mut builder_when_next_is_row,
+            &prev_registers,
+            local.delay_timer.into(),
+            next,
+        );
+        let start_registers: [AB::Expr; NUM_REGISTERS] =
+            public_values.start_registers.map(Into::into);
+        eval_registers(
+            &mut builder.when_first_row(),
+            &start_registers,
+            public_values.start_delay_timer.into(),
+            local,
+        );
+
+        // Stack. The selector marks the slot written by 2NNN or read by 00EE, so a call with a
+        // full stack or a return with an empty one has no valid selector.
+        for i in 0..STACK_DEPTH {
+            builder.assert_bool(local.stack_pointer_sel[i]);
+        }
         builder.assert_eq(
-            local.vy,
             local
-                .y_sel
-                .iter()
-                .zip_eq(local.registers.iter())
-                .map(|(&sel, &register)| sel * register)
+                .stack_pointer_sel
+                .into_iter()
+                .map(|x| x.into())
+                .sum::<AB::Expr>(),
+            local.is_call + local.is_return,
+        );
+        let prev_stack: [AB::Expr; STACK_DEPTH] = local.stack.map(Into::into);
+        let mut builder_when_next_is_row = builder.when_transition();
+        let mut builder_when_next_is_row =
+            builder_when_next_is_row.when(next.is_real + next.is_final);
+        eval_stack(
+            &mut builder_when_next_is_row,
+            &prev_stack,
+            local.stack_pointer.into(),
+            next,
+        );
+        let start_stack: [AB::Expr; STACK_DEPTH] = public_values.start_stack.map(Into::into);
+        eval_stack(
+            &mut builder.when_first_row(),
+            &start_stack,
+            public_values.start_stack_pointer.into(),
+            local,
+        );
+
+        // Index register
+        let mut builder_when_next_is_row = builder.when_transition();
+        let mut builder_when_next_is_row =
+            builder_when_next_is_row.when(next.is_real + next.is_final);
+        eval_index_register(
+            &mut builder_when_next_is_row,
+            local.index_register.into(),
+            self.quirks,
+            next,
+        );
+        eval_index_register(
+            &mut builder.when_first_row(),
+            public_values.start_index_register.into(),
+            self.quirks,
+            local,
+        );
+
+        // Timers
+        let timer_counter = ModuloCounterAir(TICKS_PER_TIMER as u32);
+        let mut builder_when_next_is_row = builder.when(next.is_real + next.is_final);
+        let mut timer_counter_builder = SubAirBuilder::new_main(
+            &mut builder_when_next_is_row,
+            vec![
+                col_map.timer_counter,
+                col_map.timer_counter_diff_inv,
+                col_map.is_timer_counter_max,
+            ],
+        );
+        timer_counter.eval(&mut timer_counter_builder);
+        builder.assert_bool(local.is_delay_timer_nonzero);
+        builder.assert_bool(local.is_sound_timer_nonzero);
+
+        let mut builder_when_next_is_row = builder.when_transition();
+        let mut builder_when_next_is_row =
+            builder_when_next_is_row.when(next.is_real + next.is_final);
+        eval_timers(
+            &mut builder_when_next_is_row,
+            local.delay_timer.into(),
+            local.sound_timer.into(),
+            local.is_timer_counter_max * next.is_real,
+            next,
+        );
+        // Segments start on a timer tick
+        eval_timers(
+            &mut builder.when_first_row(),
+            public_values.start_delay_timer.into(),
+            public_values.start_sound_timer.into(),
+            local.is_real.into(),
+            local,
+        );
+
+        // ALU
+        for i in 0..WORD_BITS {
+            builder.assert_bool(local.vx_bits[i]);
+            builder.assert_bool(local.vy_bits[i]);
+        }
+        builder.assert_eq(local.vx, from_bits::<AB>(&local.vx_bits));
+        builder.assert_eq(local.vy, from_bits::<AB>(&local.vy_bits));
+        builder.assert_bool(local.alu_flag);
+
+        let bitwise = |f: fn(AB::Expr, AB::Expr) -> AB::Expr| {
+            local
+                .vx_bits
+                .into_iter()
+                .zip_eq(local.vy_bits)
+                .enumerate()
+                .map(|(i, (a, b))| f(a.into(), b.into()) * AB::Expr::from_canonical_u32(1 << i))
+                .sum::<AB::Expr>()
+        };
+        builder.when(local.is_or).assert_eq(
+            local.alu_result,
+            bitwise(|a, b| a.clone() + b.clone() - a * b),
+        );
+        builder
+            .when(local.is_and)
+            .assert_eq(local.alu_result, bitwise(|a, b| a * b));
+        builder.when(local.is_xor).assert_eq(
+            local.alu_result,
+            bitwise(|a, b| a.clone() + b.clone() - (a * b).double()),
+        );
+
+        let word_modulus = AB::Expr::from_canonical_u32(1 << WORD_BITS);
+        builder.when(local.is_add).assert_eq(
+            local.vx + local.nn,
+            local.alu_result + local.alu_flag * word_modulus.clone(),
+        );
+        builder.when(local.is_add_xy).assert_eq(
+            local.vx + local.vy,
+            local.alu_result + local.alu_flag * word_modulus.clone(),
+        );
+        builder.when(local.is_sub_xy).assert_eq(
+            local.vx - local.vy + (AB::Expr::one() - local.alu_flag) * word_modulus.clone(),
+            local.alu_result,
+        );
+        builder.when(local.is_sub_yx).assert_eq(
+            local.vy - local.vx + (AB::Expr::one() - local.alu_flag) * word_modulus,
+            local.alu_result,
+        );
+
+        // With the shift quirk 8XY6 and 8XYE shift vy instead of vx
+        let shift_bits = if self.quirks.shift_vy {
+            local.vy_bits
+        } else {
+            local.vx_bits
+        };
+        builder.when(local.is_shift_right).assert_eq(
+            local.alu_result,
+            (1..WORD_BITS)
+                .map(|i| shift_bits[i] * AB::Expr::from_canonical_u32(1 << (i - 1)))
                 .sum::<AB::Expr>(),
         );
+        builder
+            .when(local.is_shift_right)
+            .assert_eq(local.alu_flag, shift_bits[0]);
+        builder.when(local.is_shift_left).assert_eq(
+            local.alu_result,
+            (0..WORD_BITS - 1)
+                .map(|i| shift_bits[i] * AB::Expr::from_canonical_u32(1 << (i + 1)))
+                .sum::<AB::Expr>(),
+        );
+        builder
+            .when(local.is_shift_left)
+            .assert_eq(local.alu_flag, shift_bits[WORD_BITS - 1]);
+
+        // keypad
+        for i in 0..NUM_KEYS {
+            builder.assert_bool(local.keypad[i]);
+            builder.when_first_row().assert_eq(
+                local.keypad[i],
+                public_values.start_keypad[i] + local.keypad_diff[i],
+            );
+            builder
+                .when_transition()
+                .when(next.is_real)
+                .assert_eq(next.keypad[i], local.keypad[i] + next.keypad_diff[i]);
+            builder
+                .when_ne(local.is_real, AB::Expr::one())
+                .assert_zero(local.keypad_diff[i]);
+        }
 
         // keypad selector
         for i in 0..NUM_KEYS {
@@ -141,7 +610,7 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
         }
         builder
             .when(local.is_skip_key_pressed + local.is_skip_key_not_pressed)
-            .assert_one(local.x_sel.into_iter().map(|x| x.into()).sum::<AB::Expr>());
+            .assert_one(local.vx_sel.into_iter().map(|x| x.into()).sum::<AB::Expr>());
         builder
             .when(local.is_skip_key_pressed + local.is_skip_key_not_pressed)
             .assert_eq(
@@ -191,10 +660,11 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
         // program counter
         builder
             .when_transition()
-            .when(next.is_real)
+            .when(next.is_real + next.is_final)
             .when(
                 local.is_clear_display
                     + local.is_load
+                    + local.is_add
                     + local.is_move
                     + local.is_or
                     + local.is_and
@@ -206,9 +676,9 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
                     + local.is_shift_left
                     + local.is_load_i
                     + local.is_random
-                    + local.is_draw
+                    + is_draw_done
                     + local.is_load_delay
-                    + local.is_wait_key_press
+                    + local.is_wait_key_done
                     + local.is_set_delay
                     + local.is_set_sound
                     + local.is_add_i
@@ -222,6 +692,12 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
                 local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE),
             );
 
+        builder
+            .when_transition()
+            .when(next.is_real + next.is_final)
+            .when(local.is_wait_key_press - local.is_wait_key_done + local.is_draw_wait)
+            .assert_eq(next.program_counter, local.program_counter);
+
         let stack_top = local
             .stack_pointer_sel
             .iter()
@@ -230,81 +706,246 @@ impl<AB: AirBuilder> Air<AB> for CpuChip {
             .sum::<AB::Expr>();
         builder
             .when_transition()
-            .when(next.is_real) // TODO: Check if necessary
+            .when(next.is_real + next.is_final)
             .when(local.is_return)
             .assert_eq(next.program_counter, stack_top);
 
         builder
             .when_transition()
-            .when(next.is_real)
+            .when(next.is_real + next.is_final)
             .when(local.is_jump + local.is_call)
             .assert_eq(next.program_counter, local.nnn);
 
+        // With the jump quirk BXNN adds vx, which the instruction doesn't change
+        let jump_offset = if self.quirks.jump_vx {
+            local.vx
+        } else {
+            local.registers[0]
+        };
         builder
             .when_transition()
-            .when(next.is_real)
+            .when(next.is_real + next.is_final)
             .when(local.is_jump_v0)
-            .assert_eq(next.program_counter, local.registers[0] + local.nnn);
+            .assert_eq(next.program_counter, jump_offset + local.nnn);
 
-        builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_equal)
-            .when(local.is_equal_vx_nn)
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
-        builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_not_equal)
-            .when_ne(local.is_equal_vx_nn, AB::Expr::one())
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
+        // Skips advance by one more instruction when taken
+        let skips: [(AB::Var, AB::Expr); 6] = [
+            (local.is_skip_equal, local.is_equal_vx_nn.into()),
+            (
+                local.is_skip_not_equal,
+                AB::Expr::one() - local.is_equal_vx_nn,
+            ),
+            (local.is_skip_equal_xy, local.is_equal_vx_vy.into()),
+            (
+                local.is_skip_not_equal_xy,
+                AB::Expr::one() - local.is_equal_vx_vy,
+            ),
+            (local.is_skip_key_pressed, key_vx.clone()),
+            (local.is_skip_key_not_pressed, AB::Expr::one() - key_vx),
+        ];
+        for (is_skip, is_taken) in skips {
+            builder
+                .when_transition()
+                .when(next.is_real + next.is_final)
+                .when(is_skip)
+                .assert_eq(
+                    next.program_counter,
+                    local.program_counter
+                        + AB::Expr::from_canonical_u16(OPCODE_SIZE) * (AB::Expr::one() + is_taken),
+                );
+        }
 
+        // BCD digits of vx, each range checked to 0..=9
+        builder.when(local.is_store_bcd).assert_eq(
+            local.vx,
+            local.vx_bcd0 * AB::Expr::from_canonical_u32(100)
+                + local.vx_bcd1 * AB::Expr::from_canonical_u32(10)
+                + local.vx_bcd2,
+        );
+    }
+}
+
+/// Opcode selectors with the pattern and mask of the opcodes they decode.
+fn decode_table<T: Copy>(local: &CpuCols<T>) -> [(T, u16, u16); NUM_OPCODES] {
+    [
+        (local.is_clear_display, 0x00E0, 0xFFFF),
+        (local.is_return, 0x00EE, 0xFFFF),
+        (local.is_jump, 0x1000, 0xF000),
+        (local.is_call, 0x2000, 0xF000),
+        (local.is_skip_equal, 0x3000, 0xF000),
+        (local.is_skip_not_equal, 0x4000, 0xF000),
+        (local.is_skip_equal_xy, 0x5000, 0xF00F),
+        (local.is_load, 0x6000, 0xF000),
+        (local.is_add, 0x7000, 0xF000),
+        (local.is_move, 0x8000, 0xF00F),
+        (local.is_or, 0x8001, 0xF00F),
+        (local.is_and, 0x8002, 0xF00F),
+        (local.is_xor, 0x8003, 0xF00F),
+        (local.is_add_xy, 0x8004, 0xF00F),
+        (local.is_sub_xy, 0x8005, 0xF00F),
+        (local.is_shift_right, 0x8006, 0xF00F),
+        (local.is_sub_yx, 0x8007, 0xF00F),
+        (local.is_shift_left, 0x800E, 0xF00F),
+        (local.is_skip_not_equal_xy, 0x9000, 0xF00F),
+        (local.is_load_i, 0xA000, 0xF000),
+        (local.is_jump_v0, 0xB000, 0xF000),
+        (local.is_random, 0xC000, 0xF000),
+        (local.is_draw, 0xD000, 0xF000),
+        (local.is_skip_key_pressed, 0xE09E, 0xF0FF),
+        (local.is_skip_key_not_pressed, 0xE0A1, 0xF0FF),
+        (local.is_load_delay, 0xF007, 0xF0FF),
+        (local.is_wait_key_press, 0xF00A, 0xF0FF),
+        (local.is_set_delay, 0xF015, 0xF0FF),
+        (local.is_set_sound, 0xF018, 0xF0FF),
+        (local.is_add_i, 0xF01E, 0xF0FF),
+        (local.is_load_font, 0xF029, 0xF0FF),
+        (local.is_store_bcd, 0xF033, 0xF0FF),
+        (local.is_store_registers, 0xF055, 0xF0FF),
+        (local.is_load_memory, 0xF065, 0xF0FF),
+    ]
+}
+
+fn from_bits<AB: AirBuilder>(bits: &[AB::Var; WORD_BITS]) -> AB::Expr {
+    bits.iter()
+        .enumerate()
+        .map(|(i, &bit)| bit * AB::Expr::from_canonical_u32(1 << i))
+        .sum()
+}
+
+/// Constrains the operands of the instruction in `local` and that it only changes the registers
+/// it writes, given the registers and delay timer before it.
+fn eval_registers<AB: AirBuilder>(
+    builder: &mut AB,
+    prev: &[AB::Expr; NUM_REGISTERS],
+    prev_delay_timer: AB::Expr,
+    local: &CpuCols<AB::Var>,
+) {
+    builder.assert_eq(
+        local.vx,
+        local
+            .x_sel
+            .iter()
+            .zip_eq(prev.iter())
+            .map(|(&sel, register)| register.clone() * sel)
+            .sum::<AB::Expr>(),
+    );
+    builder.assert_eq(
+        local.vy,
+        local
+            .y_sel
+            .iter()
+            .zip_eq(prev.iter())
+            .map(|(&sel, register)| register.clone() * sel)
+            .sum::<AB::Expr>(),
+    );
+
+    builder
+        .when(local.is_load_delay)
+        .assert_eq(local.alu_result, prev_delay_timer);
+
+    for i in 0..NUM_REGISTERS {
         builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_equal_xy)
-            .when(local.is_equal_vx_vy)
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
-        builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_not_equal_xy)
-            .when_ne(local.is_equal_vx_vy, AB::Expr::one())
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
-        builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_key_pressed)
-            .when(key_vx.clone())
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
-        builder
-            .when_transition()
-            .when(next.is_real)
-            .when(local.is_skip_key_not_pressed)
-            .when_ne(key_vx, AB::Expr::one())
-            .assert_eq(
-                next.program_counter,
-                local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE).double(),
-            );
+            .when_ne(local.write_sel[i], AB::Expr::one())
+            .assert_eq(local.registers[i], prev[i].clone());
+    }
+}
 
-        // lte_x_sel only on 2 opcodes
-        // builder.when(local.is_store_registers + local.is_load_memory).assert
+/// Constrains the index register after the instruction in `local` given its value before it.
+fn eval_index_register<AB: AirBuilder>(
+    builder: &mut AB,
+    prev_index_register: AB::Expr,
+    quirks: Quirks,
+    local: &CpuCols<AB::Var>,
+) {
+    let font_address = AB::Expr::from_canonical_u16(FONTSET_START_ADDRESS)
+        + local.vx * AB::Expr::from_canonical_usize(FONT_SIZE);
+    let mut index_register = prev_index_register.clone()
+        + local.is_load_i * (local.nnn - prev_index_register.clone())
+        + local.is_add_i * local.vx
+        + local.is_load_font * (font_address - prev_index_register);
+    // With the memory quirk FX55 and FX65 leave I past the last register
+    if quirks.memory_increment {
+        index_register += (local.is_store_registers + local.is_load_memory)
+            * (x_index::<AB>(local) + AB::Expr::one());
+    }
+    builder.assert_eq(local.index_register, index_register);
+}
+
+/// Index of vx, given by its one-hot selector.
+fn x_index<AB: AirBuilder>(local: &CpuCols<AB::Var>) -> AB::Expr {
+    local
+        .x_sel
+        .iter()
+        .enumerate()
+        .map(|(i, &sel)| sel * AB::Expr::from_canonical_usize(i))
+        .sum()
+}
+
+/// Constrains the stack and stack pointer after the instruction in `local` given their values
+/// before it.
+fn eval_stack<AB: AirBuilder>(
+    builder: &mut AB,
+    prev_stack: &[AB::Expr; STACK_DEPTH],
+    prev_stack_pointer: AB::Expr,
+    local: &CpuCols<AB::Var>,
+) {
+    builder.assert_eq(
+        local.stack_pointer,
+        prev_stack_pointer.clone() + local.is_call - local.is_return,
+    );
+
+    // Calls write to the slot at the stack pointer and returns read from the one below it
+    builder.assert_eq(
+        local
+            .stack_pointer_sel
+            .into_iter()
+            .enumerate()
+            .map(|(i, sel)| AB::Expr::from_canonical_usize(i) * sel)
+            .sum::<AB::Expr>(),
+        (local.is_call + local.is_return) * prev_stack_pointer - local.is_return,
+    );
 
-        // TODO: Constrain bcd_i
+    let return_address = local.program_counter + AB::Expr::from_canonical_u16(OPCODE_SIZE);
+    for i in 0..STACK_DEPTH {
+        builder.when(local.is_call).assert_eq(
+            local.stack[i],
+            prev_stack[i].clone()
+                + local.stack_pointer_sel[i] * (return_address.clone() - prev_stack[i].clone()),
+        );
+        builder
+            .when_ne(local.is_call, AB::Expr::one())
+            .assert_eq(local.stack[i], prev_stack[i].clone());
     }
 }
+
+/// Constrains the timers after the instruction in `local` given their values before it. FX15 and
+/// FX18 load them from vx and on timer ticks the non-zero ones decrement.
+fn eval_timers<AB: AirBuilder>(
+    builder: &mut AB,
+    prev_delay_timer: AB::Expr,
+    prev_sound_timer: AB::Expr,
+    is_tick: AB::Expr,
+    local: &CpuCols<AB::Var>,
+) {
+    let delay_timer = prev_delay_timer.clone() + (local.vx - prev_delay_timer) * local.is_set_delay;
+    builder.assert_eq(
+        local.is_delay_timer_nonzero,
+        delay_timer.clone() * local.delay_timer_inv,
+    );
+    builder.assert_zero(delay_timer.clone() * (AB::Expr::one() - local.is_delay_timer_nonzero));
+    builder.assert_eq(
+        local.delay_timer,
+        delay_timer - is_tick.clone() * local.is_delay_timer_nonzero,
+    );
+
+    let sound_timer = prev_sound_timer.clone() + (local.vx - prev_sound_timer) * local.is_set_sound;
+    builder.assert_eq(
+        local.is_sound_timer_nonzero,
+        sound_timer.clone() * local.sound_timer_inv,
+    );
+    builder.assert_zero(sound_timer.clone() * (AB::Expr::one() - local.is_sound_timer_nonzero));
+    builder.assert_eq(
+        local.sound_timer,
+        sound_timer - is_tick * local.is_sound_timer_nonzero,
+    );
+}
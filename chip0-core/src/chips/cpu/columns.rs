@@ -1,6 +1,12 @@
 use chip8_core::constants::{NUM_KEYS, NUM_REGISTERS, STACK_DEPTH};
 use p3_derive::Columnar;
 
+use crate::chips::draw::columns::WORD_BITS;
+
+pub const NIBBLE_BITS: usize = 4;
+/// Offset that moves the BCD digits 0..=9 to the top of the byte range
+pub const BCD_DIGIT_OFFSET: u32 = (1 << WORD_BITS) - 10;
+
 #[repr(C)]
 #[derive(Columnar, Clone, Default)]
 pub struct CpuCols<T> {
@@ -11,6 +17,8 @@ pub struct CpuCols<T> {
     pub opcode_lo: T,
 
     pub opcode: T,
+    // Highest nibble of the opcode
+    pub opcode_top: T,
 
     pub is_clear_display: T,
     pub is_return: T,
@@ -54,8 +62,28 @@ pub struct CpuCols<T> {
     pub stack_pointer: T,
     pub delay_timer: T,
     pub sound_timer: T,
+    // clk modulo TICKS_PER_TIMER, timers tick when it is zero
+    pub timer_counter: T,
+    pub timer_counter_diff_inv: T,
+    pub is_timer_counter_max: T,
+    // Whether timer_counter is zero, which is where DXYN stops waiting with the display wait quirk
+    pub is_timer_tick: T,
+    // Whether the timers are non-zero before the tick
+    pub delay_timer_inv: T,
+    pub is_delay_timer_nonzero: T,
+    pub sound_timer_inv: T,
+    pub is_sound_timer_nonzero: T,
     pub keypad: [T; NUM_KEYS],
+    // LFSR state before and after the instruction, which only CXNN advances
+    pub prev_random_state: T,
+    pub random_state: T,
+    // Key FX0A waits to be released plus one, or zero, before and after the instruction
+    pub prev_wait_key: T,
+    pub wait_key: T,
+    // Change of the key state from the previous cycle
+    pub keypad_diff: [T; NUM_KEYS],
 
+    // Stack slot written by 2NNN or read by 00EE
     pub stack_pointer_sel: [T; STACK_DEPTH],
 
     pub x: T,
@@ -66,6 +94,8 @@ pub struct CpuCols<T> {
 
     pub x_sel: [T; NUM_REGISTERS],
     pub y_sel: [T; NUM_REGISTERS],
+    // Registers written by the instruction
+    pub write_sel: [T; NUM_REGISTERS],
 
     pub vx: T,
     pub vy: T,
@@ -85,20 +115,26 @@ pub struct CpuCols<T> {
     pub diff_vx_vy_inv: T,
     pub is_equal_vx_vy: T,
 
-    // pub or_vx_vy: T,
-    // pub and_vx_vy: T,
-    // pub xor_vx_vy: T,
-    // pub add_vx_vy: T,
-    // pub add_vx_vy_carry: T,
-    // pub sub_vx_vy: T,
-    // pub sub_vx_vy_borrow: T,
-    // pub shr_vx: T,
-    // pub shr_vx_flag: T,
-    // pub sub_vy_vx: T,
-    // pub sub_vy_vx_borrow: T,
-    // pub shl_vx: T,
-    // pub shl_vx_flag: T,
-    // pub add_vi_vx: T,
+    pub vx_bits: [T; WORD_BITS],
+    pub vy_bits: [T; WORD_BITS],
+
+    // Value written to vx, and the carry of 7XNN or the flag written to VF by 8XYN
+    // instructions
+    pub alu_result: T,
+    pub alu_flag: T,
+
+    // DXYN with a non-zero sprite height, which is the only draw handled by the draw chip
+    pub n_inv: T,
+    pub is_draw_nonempty: T,
+    // DXYN stalled by the display wait quirk until the next timer tick
+    pub is_draw_wait: T,
+
+    // FX0A waits for a key to go down while idle and then for the selected key to be released
+    pub is_wait_key_idle: T,
+    pub is_wait_key_held: T,
+    pub wait_key_sel: [T; NUM_KEYS],
+    pub is_wait_key_done: T,
+
     // TODO: start and end
     pub is_first: T,
     pub is_final: T,
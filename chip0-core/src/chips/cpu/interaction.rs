@@ -1,9 +1,9 @@
 use chip8_core::constants::{FLAG_REGISTER, NUM_KEYS, NUM_REGISTERS};
-use p3_air::VirtualPairCol;
+use p3_air::{AirBuilderWithPublicValues, VirtualPairCol};
 use p3_field::Field;
 use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
 
-use crate::chips::cpu::columns::CpuCols;
+use crate::chips::cpu::columns::{CpuCols, BCD_DIGIT_OFFSET, NIBBLE_BITS};
 
 use super::CpuChip;
 
@@ -20,7 +20,7 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.registers[FLAG_REGISTER]),
                 ],
-                count: VirtualPairCol::single_main(col_map.is_draw),
+                count: VirtualPairCol::single_main(col_map.is_draw_nonempty),
                 argument_index: self.bus_draw,
             },
             Interaction {
@@ -28,6 +28,7 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::single_main(col_map.program_counter),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.opcode_hi),
+                    VirtualPairCol::constant(F::one()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_real),
                 argument_index: self.bus_memory,
@@ -37,6 +38,7 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::new_main(vec![(col_map.program_counter, F::one())], F::one()),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.opcode_lo),
+                    VirtualPairCol::constant(F::one()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_real),
                 argument_index: self.bus_memory,
@@ -46,6 +48,7 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::single_main(col_map.index_register),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.vx_bcd0),
+                    VirtualPairCol::constant(F::zero()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_store_bcd),
                 argument_index: self.bus_memory,
@@ -55,6 +58,7 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::new_main(vec![(col_map.index_register, F::one())], F::one()),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.vx_bcd1),
+                    VirtualPairCol::constant(F::zero()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_store_bcd),
                 argument_index: self.bus_memory,
@@ -64,36 +68,48 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                     VirtualPairCol::new_main(vec![(col_map.index_register, F::one())], F::two()),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.vx_bcd2),
+                    VirtualPairCol::constant(F::zero()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_store_bcd),
                 argument_index: self.bus_memory,
             },
         ];
 
-        interactions.extend((0..NUM_REGISTERS).map(|i| Interaction {
-            fields: vec![
+        // FX55 and FX65 transfer vi from I + i. With the memory quirk the index register already
+        // holds I + x + 1.
+        let register_address = |i: usize| {
+            if self.quirks.memory_increment {
+                let mut column_weights = vec![(col_map.index_register, F::one())];
+                column_weights.extend(
+                    (0..NUM_REGISTERS).map(|j| (col_map.x_sel[j], -F::from_canonical_usize(j))),
+                );
+                VirtualPairCol::new_main(column_weights, F::from_canonical_usize(i) - F::one())
+            } else {
                 VirtualPairCol::new_main(
                     vec![(col_map.index_register, F::one())],
                     F::from_canonical_usize(i),
-                ),
+                )
+            }
+        };
+        interactions.extend((0..NUM_REGISTERS).map(|i| Interaction {
+            fields: vec![
+                register_address(i),
                 VirtualPairCol::single_main(col_map.clk),
                 VirtualPairCol::single_main(col_map.registers[i]),
+                VirtualPairCol::single_main(col_map.is_load_memory),
             ],
-            // TODO: load/store registers
             count: VirtualPairCol::single_main(col_map.lte_x_sel[i]),
             argument_index: self.bus_memory,
         }));
 
-        // TODO: keypad interactions
-        // interactions.extend((0..NUM_KEYS).map(|i| Interaction {
-        //     fields: vec![
-        //         VirtualPairCol::single_main(col_map.clk),
-        //         VirtualPairCol::constant(F::from_canonical_usize(i)),
-        //         VirtualPairCol::single_main(col_map.keypad[i]),
-        //     ],
-        //     count: VirtualPairCol::single_main(col_map.keypad[i]),
-        //     argument_index: self.bus_memory,
-        // }));
+        interactions.extend((0..NUM_KEYS).map(|i| Interaction {
+            fields: vec![
+                VirtualPairCol::single_main(col_map.clk),
+                VirtualPairCol::constant(F::from_canonical_usize(i)),
+            ],
+            count: VirtualPairCol::single_main(col_map.keypad_diff[i]),
+            argument_index: self.bus_keypad,
+        }));
 
         interactions
     }
@@ -104,15 +120,16 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
         main_indices: &[usize],
     ) -> Vec<Interaction<F>> {
         let col_map = CpuCols::from_slice(main_indices);
-        vec![
+        let mut interactions = vec![
             Interaction {
                 fields: vec![
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.index_register),
                     VirtualPairCol::single_main(col_map.vx),
                     VirtualPairCol::single_main(col_map.vy),
+                    VirtualPairCol::single_main(col_map.n),
                 ],
-                count: VirtualPairCol::single_main(col_map.is_draw),
+                count: VirtualPairCol::single_main(col_map.is_draw_nonempty),
                 argument_index: self.bus_draw,
             },
             Interaction {
@@ -120,7 +137,51 @@ impl<F: Field> BaseInteractionAir<F> for CpuChip {
                 count: VirtualPairCol::single_main(col_map.is_clear_display),
                 argument_index: self.bus_clear,
             },
-        ]
+            Interaction {
+                fields: vec![VirtualPairCol::new_main(
+                    vec![(col_map.n, F::from_canonical_u32(1 << NIBBLE_BITS))],
+                    F::zero(),
+                )],
+                count: VirtualPairCol::single_main(col_map.is_real),
+                argument_index: self.bus_range,
+            },
+            Interaction {
+                fields: vec![VirtualPairCol::single_main(col_map.alu_result)],
+                count: VirtualPairCol::sum_main(vec![
+                    col_map.is_add,
+                    col_map.is_add_xy,
+                    col_map.is_sub_xy,
+                    col_map.is_sub_yx,
+                    col_map.is_random,
+                ]),
+                argument_index: self.bus_range,
+            },
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_main(col_map.prev_random_state),
+                    VirtualPairCol::single_main(col_map.random_state),
+                    VirtualPairCol::single_main(col_map.nn),
+                    VirtualPairCol::single_main(col_map.alu_result),
+                ],
+                count: VirtualPairCol::single_main(col_map.is_random),
+                argument_index: self.bus_random,
+            },
+        ];
+
+        // A digit d is in 0..=9 when both d and d + 246 are bytes
+        interactions.extend(
+            [col_map.vx_bcd0, col_map.vx_bcd1, col_map.vx_bcd2]
+                .into_iter()
+                .flat_map(|digit| {
+                    [F::zero(), F::from_canonical_u32(BCD_DIGIT_OFFSET)].map(|offset| Interaction {
+                        fields: vec![VirtualPairCol::new_main(vec![(digit, F::one())], offset)],
+                        count: VirtualPairCol::single_main(col_map.is_store_bcd),
+                        argument_index: self.bus_range,
+                    })
+                }),
+        );
+
+        interactions
     }
 }
 
@@ -136,4 +197,4 @@ impl<F: Field> InteractionAir<F> for CpuChip {
     }
 }
 
-impl<AB: InteractionAirBuilder> Rap<AB> for CpuChip {}
+impl<AB: InteractionAirBuilder + AirBuilderWithPublicValues> Rap<AB> for CpuChip {}
@@ -2,6 +2,7 @@ pub mod air;
 pub mod columns;
 pub mod interaction;
 
+use chip8_core::quirks::Quirks;
 #[cfg(feature = "trace-writer")]
 use p3_air_util::TraceWriter;
 #[cfg(feature = "trace-writer")]
@@ -16,15 +17,29 @@ pub struct CpuChip {
     bus_draw: usize,
     bus_memory: usize,
     bus_keypad: usize,
+    bus_range: usize,
+    bus_random: usize,
+    quirks: Quirks,
 }
 
 impl CpuChip {
-    pub fn new(bus_clear: usize, bus_draw: usize, bus_memory: usize, bus_keypad: usize) -> Self {
+    pub fn new(
+        bus_clear: usize,
+        bus_draw: usize,
+        bus_memory: usize,
+        bus_keypad: usize,
+        bus_range: usize,
+        bus_random: usize,
+        quirks: Quirks,
+    ) -> Self {
         Self {
             bus_clear,
             bus_draw,
             bus_memory,
             bus_keypad,
+            bus_range,
+            bus_random,
+            quirks,
         }
     }
 }
@@ -1,10 +1,11 @@
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use core::borrow::Borrow;
 use itertools::Itertools;
 use p3_air::{Air, AirBuilder, BaseAir};
 use p3_field::AbstractField;
 use p3_matrix::Matrix;
 
-use super::columns::DrawCols;
+use super::columns::{DrawCols, WORD_BITS};
 use super::DrawChip;
 
 impl<F> BaseAir<F> for DrawChip {
@@ -22,46 +23,83 @@ impl<AB: AirBuilder> Air<AB> for DrawChip {
         let next: &DrawCols<AB::Var> = (*next).borrow();
 
         builder.assert_bool(local.is_real);
-        // TODO: Fill diagonally for proper constraints
         builder.assert_bool(local.is_first);
         builder.assert_bool(local.is_last);
+        builder.assert_bool(local.is_first_inner);
+        builder.when(local.is_first).assert_one(local.is_real);
+        builder.when(local.is_last).assert_one(local.is_real);
 
-        builder
-            .when(local.is_first)
-            .assert_eq(local.xs, AB::Expr::zero());
-        builder
-            .when(local.is_first)
-            .assert_eq(local.ys, AB::Expr::zero());
-        builder
-            .when(local.is_first)
-            .assert_eq(local.register_flag, local.pixel * local.frame_buffer_y_x);
-
-        // Constraint clk
+        // Real rows form a prefix of the trace and every draw runs until its last row
         builder
             .when_transition()
-            .when_ne(local.is_last, AB::Expr::one())
-            .assert_eq(next.clk, local.clk);
-
-        // TODO: More constraints
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(next.is_real);
         builder
+            .when_transition()
             .when(local.is_real)
             .when_ne(local.is_last, AB::Expr::one())
-            .assert_eq(
-                next.ys * AB::Expr::from_canonical_u8(8) + next.xs,
-                local.ys * AB::Expr::from_canonical_u8(8) + local.xs + AB::Expr::one(),
-            );
+            .assert_one(next.is_real);
         builder
+            .when_last_row()
             .when(local.is_real)
-            .assert_eq(local.flipped, local.pixel * local.frame_buffer_y_x);
+            .assert_one(local.is_last);
+        builder
+            .when_first_row()
+            .assert_eq(local.is_first, local.is_real);
         builder
             .when_transition()
-            .when_ne(local.is_last, AB::Expr::one())
-            .assert_eq(
-                next.register_flag,
-                local.register_flag + next.pixel * next.frame_buffer_y_x,
-            );
+            .assert_eq(next.is_first, local.is_last * next.is_real);
+
+        // Pixels are visited row by row and the first pixel of each row reads the sprite byte
+        for i in 0..WORD_BITS {
+            builder.assert_bool(local.sel_7_minus_xs[i]);
+        }
+        builder.assert_eq(
+            local
+                .sel_7_minus_xs
+                .into_iter()
+                .map(|sel| sel.into())
+                .sum::<AB::Expr>(),
+            local.is_real,
+        );
+        builder.assert_eq(
+            local.xs,
+            local
+                .sel_7_minus_xs
+                .into_iter()
+                .enumerate()
+                .map(|(i, sel)| AB::Expr::from_canonical_usize(WORD_BITS - 1 - i) * sel)
+                .sum::<AB::Expr>(),
+        );
+        builder.assert_eq(local.is_first_inner, local.sel_7_minus_xs[WORD_BITS - 1]);
 
-        builder.when(local.is_real).assert_eq(
+        builder
+            .when(local.is_first)
+            .assert_eq(local.xs, AB::Expr::zero());
+        builder
+            .when(local.is_first)
+            .assert_eq(local.ys, AB::Expr::zero());
+        builder
+            .when(local.is_last)
+            .assert_eq(local.xs, AB::Expr::from_canonical_usize(WORD_BITS - 1));
+        builder
+            .when(local.is_last)
+            .assert_eq(local.ys, local.n - AB::Expr::one());
+
+        let mut builder_when_same_sprite_row = builder.when_transition();
+        let mut builder_when_same_sprite_row =
+            builder_when_same_sprite_row.when_ne(local.is_last, AB::Expr::one());
+        let mut builder_when_same_sprite_row =
+            builder_when_same_sprite_row.when_ne(next.is_first_inner, AB::Expr::one());
+        builder_when_same_sprite_row.assert_eq(next.pixels, local.pixels);
+        builder_when_same_sprite_row.assert_eq(next.y, local.y);
+        builder_when_same_sprite_row.assert_eq(next.is_y_wrapped, local.is_y_wrapped);
+
+        for i in 0..WORD_BITS {
+            builder.assert_bool(local.pixels_bits[i]);
+        }
+        builder.assert_eq(local.pixels, from_bits::<AB>(&local.pixels_bits));
+        builder.assert_eq(
             local.pixel,
             local
                 .pixels_bits
@@ -70,6 +108,70 @@ impl<AB: AirBuilder> Air<AB> for DrawChip {
                 .map(|(b, sel)| b * sel)
                 .sum::<AB::Expr>(),
         );
-        // TODO: Constrain x, y, is_first_inner
+
+        // Coordinates start at vx % width and vy % height and wrap around the edges. The wrapped
+        // coordinates are range checked, which fixes the wrap flags.
+        for i in 0..WORD_BITS {
+            builder.assert_bool(local.register_x_bits[i]);
+            builder.assert_bool(local.register_y_bits[i]);
+        }
+        builder.assert_eq(local.register_x, from_bits::<AB>(&local.register_x_bits));
+        builder.assert_eq(local.register_y, from_bits::<AB>(&local.register_y_bits));
+        let x0 = from_bits::<AB>(&local.register_x_bits[..DISPLAY_WIDTH.ilog2() as usize]);
+        let y0 = from_bits::<AB>(&local.register_y_bits[..DISPLAY_HEIGHT.ilog2() as usize]);
+
+        builder.assert_bool(local.is_x_wrapped);
+        builder.assert_bool(local.is_y_wrapped);
+        builder.assert_eq(
+            local.x,
+            x0 + local.xs - local.is_x_wrapped * AB::Expr::from_canonical_usize(DISPLAY_WIDTH),
+        );
+        builder.assert_eq(
+            local.y,
+            y0 + local.ys - local.is_y_wrapped * AB::Expr::from_canonical_usize(DISPLAY_HEIGHT),
+        );
+
+        // Pixels past the edges are either wrapped or clipped
+        let is_visible = if self.clip {
+            local.is_real
+                * (AB::Expr::one() - local.is_x_wrapped)
+                * (AB::Expr::one() - local.is_y_wrapped)
+        } else {
+            local.is_real.into()
+        };
+        builder.assert_eq(local.is_visible, is_visible);
+        builder.assert_eq(local.is_flip, local.pixel * local.is_visible);
+
+        // Collision flag is set if any flipped pixel was on
+        builder.assert_bool(local.frame_buffer_y_x);
+        builder.assert_eq(local.flipped, local.is_flip * local.frame_buffer_y_x);
+        builder
+            .when(local.is_first)
+            .assert_eq(local.register_flag, local.flipped);
+
+        // Rows of a draw share the instruction
+        let mut builder_when_same_draw = builder.when_transition();
+        let mut builder_when_same_draw =
+            builder_when_same_draw.when_ne(local.is_last, AB::Expr::one());
+        builder_when_same_draw.assert_eq(next.clk, local.clk);
+        builder_when_same_draw.assert_eq(next.register_x, local.register_x);
+        builder_when_same_draw.assert_eq(next.register_y, local.register_y);
+        builder_when_same_draw.assert_eq(next.index_register, local.index_register);
+        builder_when_same_draw.assert_eq(next.n, local.n);
+        builder_when_same_draw.assert_eq(
+            next.ys * AB::Expr::from_canonical_usize(WORD_BITS) + next.xs,
+            local.ys * AB::Expr::from_canonical_usize(WORD_BITS) + local.xs + AB::Expr::one(),
+        );
+        builder_when_same_draw.assert_eq(
+            next.register_flag,
+            local.register_flag + next.flipped - local.register_flag * next.flipped,
+        );
     }
 }
+
+fn from_bits<AB: AirBuilder>(bits: &[AB::Var]) -> AB::Expr {
+    bits.iter()
+        .enumerate()
+        .map(|(i, &bit)| bit * AB::Expr::from_canonical_u32(1 << i))
+        .sum()
+}
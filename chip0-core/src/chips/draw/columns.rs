@@ -6,23 +6,35 @@ pub const WORD_BITS: usize = 8;
 #[derive(Columnar, Default, Clone)]
 pub struct DrawCols<T> {
     pub is_real: T,
-    // TODO: start and end
     pub is_first: T,
     pub is_last: T,
 
-    // TODO: Replace with is_xs_0
+    // First pixel of a sprite row
     pub is_first_inner: T,
 
     pub clk: T,
     pub register_x: T,
     pub register_y: T,
     pub index_register: T,
+    // Sprite height
+    pub n: T,
+    pub register_x_bits: [T; WORD_BITS],
+    pub register_y_bits: [T; WORD_BITS],
+
     pub ys: T,
     pub y: T,
+    // Whether the sprite row wraps past the bottom edge
+    pub is_y_wrapped: T,
     pub pixels: T,
     pub xs: T,
     pub x: T,
+    // Whether the pixel wraps past the right edge
+    pub is_x_wrapped: T,
     pub pixel: T,
+    // Whether the pixel is on screen, always true unless sprites are clipped
+    pub is_visible: T,
+    // Whether the pixel flips the frame buffer
+    pub is_flip: T,
     pub frame_buffer_y_x: T,
     pub flipped: T,
     pub register_flag: T,
@@ -1,9 +1,12 @@
-use chip8_core::constants::DISPLAY_WIDTH;
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use p3_air::VirtualPairCol;
 use p3_field::Field;
 use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
 
-use super::{columns::DrawCols, DrawChip};
+use super::{
+    columns::{DrawCols, WORD_BITS},
+    DrawChip,
+};
 
 impl<F: Field> BaseInteractionAir<F> for DrawChip {
     fn receives_from_indices(
@@ -19,6 +22,7 @@ impl<F: Field> BaseInteractionAir<F> for DrawChip {
                     VirtualPairCol::single_main(col_map.index_register),
                     VirtualPairCol::single_main(col_map.register_x),
                     VirtualPairCol::single_main(col_map.register_y),
+                    VirtualPairCol::single_main(col_map.n),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_first),
                 argument_index: self.bus_draw,
@@ -35,11 +39,12 @@ impl<F: Field> BaseInteractionAir<F> for DrawChip {
                     ),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.frame_buffer_y_x),
+                    VirtualPairCol::constant(F::one()),
                 ],
-                count: VirtualPairCol::single_main(col_map.is_real),
+                count: VirtualPairCol::single_main(col_map.is_visible),
                 argument_index: self.bus_frame_buffer,
             },
-            // Flip frame_buffer[y][x] if pixel is set
+            // Flip frame_buffer[y][x] if pixel is set and visible
             Interaction {
                 fields: vec![
                     VirtualPairCol::new_main(
@@ -51,8 +56,9 @@ impl<F: Field> BaseInteractionAir<F> for DrawChip {
                     ),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::new_main(vec![(col_map.frame_buffer_y_x, -F::one())], F::one()),
+                    VirtualPairCol::constant(F::zero()),
                 ],
-                count: VirtualPairCol::single_main(col_map.pixel),
+                count: VirtualPairCol::single_main(col_map.is_flip),
                 argument_index: self.bus_frame_buffer,
             },
             Interaction {
@@ -60,6 +66,7 @@ impl<F: Field> BaseInteractionAir<F> for DrawChip {
                     VirtualPairCol::sum_main(vec![col_map.index_register, col_map.ys]),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.pixels),
+                    VirtualPairCol::constant(F::one()),
                 ],
                 count: VirtualPairCol::single_main(col_map.is_first_inner),
                 argument_index: self.bus_memory,
@@ -73,14 +80,37 @@ impl<F: Field> BaseInteractionAir<F> for DrawChip {
         main_indices: &[usize],
     ) -> Vec<Interaction<F>> {
         let col_map = DrawCols::from_slice(main_indices);
-        vec![Interaction {
+        let mut interactions = vec![Interaction {
             fields: vec![
                 VirtualPairCol::single_main(col_map.clk),
                 VirtualPairCol::single_main(col_map.register_flag),
             ],
             count: VirtualPairCol::single_main(col_map.is_last),
             argument_index: self.bus_draw,
-        }]
+        }];
+
+        // A coordinate c is below the screen size s when both c and c + 256 - s are bytes
+        interactions.extend(
+            [
+                (col_map.x, DISPLAY_WIDTH, col_map.is_real),
+                (col_map.y, DISPLAY_HEIGHT, col_map.is_first_inner),
+            ]
+            .into_iter()
+            .flat_map(|(coordinate, size, count)| {
+                [F::zero(), F::from_canonical_usize((1 << WORD_BITS) - size)].map(|offset| {
+                    Interaction {
+                        fields: vec![VirtualPairCol::new_main(
+                            vec![(coordinate, F::one())],
+                            offset,
+                        )],
+                        count: VirtualPairCol::single_main(count),
+                        argument_index: self.bus_range,
+                    }
+                })
+            }),
+        );
+
+        interactions
     }
 }
 
@@ -15,14 +15,24 @@ pub struct DrawChip {
     bus_draw: usize,
     bus_frame_buffer: usize,
     bus_memory: usize,
+    bus_range: usize,
+    clip: bool,
 }
 
 impl DrawChip {
-    pub fn new(bus_draw: usize, bus_frame_buffer: usize, bus_memory: usize) -> Self {
+    pub fn new(
+        bus_draw: usize,
+        bus_frame_buffer: usize,
+        bus_memory: usize,
+        bus_range: usize,
+        clip: bool,
+    ) -> Self {
         Self {
             bus_draw,
             bus_frame_buffer,
             bus_memory,
+            bus_range,
+            clip,
         }
     }
 }
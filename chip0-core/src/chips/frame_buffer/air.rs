@@ -1,8 +1,11 @@
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use core::borrow::Borrow;
-use p3_air::{Air, AirBuilder, BaseAir};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
 use p3_field::AbstractField;
 use p3_matrix::Matrix;
 
+use crate::public_values::{PublicValues, FRAME_BUFFER_SIZE};
+
 use super::columns::FrameBufferCols;
 use super::FrameBufferChip;
 
@@ -12,7 +15,7 @@ impl<F> BaseAir<F> for FrameBufferChip {
     }
 }
 
-impl<AB: AirBuilder> Air<AB> for FrameBufferChip {
+impl<AB: AirBuilderWithPublicValues> Air<AB> for FrameBufferChip {
     fn eval(&self, builder: &mut AB) {
         let main = builder.main();
         let local = main.row_slice(0);
@@ -20,27 +23,50 @@ impl<AB: AirBuilder> Air<AB> for FrameBufferChip {
         let local: &FrameBufferCols<AB::Var> = (*local).borrow();
         let next: &FrameBufferCols<AB::Var> = (*next).borrow();
 
+        let public_values = builder.public_values().to_vec();
+        let public_values: &PublicValues<AB::PublicVar> = public_values.as_slice().borrow();
+
+        builder.assert_bool(local.is_init);
         builder.assert_bool(local.is_read);
         builder.assert_bool(local.is_write);
+        builder.assert_bool(local.is_final);
+
+        let local_is_real = local.is_init + local.is_read + local.is_write;
+        let next_is_real = next.is_init + next.is_read + next.is_write;
+        builder.assert_bool(local_is_real.clone());
+
+        // Real rows form a prefix of the trace
+        builder
+            .when_transition()
+            .when_ne(local_is_real.clone(), AB::Expr::one())
+            .assert_zero(next_is_real.clone());
+
+        // Every address starts with its value at the start of the segment
+        builder.when_first_row().assert_one(local.is_init);
+        builder.when_first_row().assert_zero(local.addr);
+        builder.when(local.is_init).assert_zero(local.clk);
 
-        builder.assert_zero(local.is_read * local.is_write);
         builder.assert_bool(local.addr_unchanged);
+        builder
+            .when_transition()
+            .when(next_is_real.clone())
+            .assert_eq(next.addr_unchanged, AB::Expr::one() - next.is_init);
 
         builder
             .when_transition()
             .when(next.addr_unchanged)
             .assert_eq(local.addr, next.addr);
+        builder
+            .when_transition()
+            .when(next.is_init)
+            .assert_eq(next.addr, local.addr + AB::Expr::one());
 
         let diff = next.diff_limb_lo + next.diff_limb_hi * AB::Expr::from_canonical_u32(1 << 8);
         builder
             .when_transition()
             .when(next.addr_unchanged)
-            .assert_eq(diff.clone(), next.clk - local.clk);
-        builder
-            .when_transition()
-            .when(next.is_read + next.is_write)
-            .when_ne(next.addr_unchanged, AB::Expr::one())
-            .assert_eq(diff, next.addr - local.addr - AB::Expr::one());
+            .when_ne(local.is_init, AB::Expr::one())
+            .assert_eq(diff, next.clk - local.clk);
 
         // TODO: Do I need this?
         builder
@@ -48,5 +74,90 @@ impl<AB: AirBuilder> Air<AB> for FrameBufferChip {
             .when(next.addr_unchanged)
             .when(next.is_read)
             .assert_eq(local.value, next.value);
+
+        // The last access of every address is final and all addresses are covered
+        builder
+            .when_transition()
+            .when(local_is_real.clone())
+            .assert_eq(local.is_final, AB::Expr::one() - next.addr_unchanged);
+        builder
+            .when_last_row()
+            .assert_eq(local.is_final, local_is_real.clone());
+        builder
+            .when_ne(local_is_real.clone(), AB::Expr::one())
+            .assert_zero(local.is_final);
+        builder
+            .when_transition()
+            .when(local_is_real.clone())
+            .when_ne(next_is_real, AB::Expr::one())
+            .assert_eq(
+                local.addr,
+                AB::Expr::from_canonical_usize(FRAME_BUFFER_SIZE - 1),
+            );
+        builder
+            .when_last_row()
+            .when(local_is_real.clone())
+            .assert_eq(
+                local.addr,
+                AB::Expr::from_canonical_usize(FRAME_BUFFER_SIZE - 1),
+            );
+
+        // Decompose address into coordinates
+        for x in 0..DISPLAY_WIDTH {
+            builder.assert_bool(local.x_sel[x]);
+        }
+        for y in 0..DISPLAY_HEIGHT {
+            builder.assert_bool(local.y_sel[y]);
+        }
+        builder.assert_eq(
+            local.x_sel.into_iter().map(|x| x.into()).sum::<AB::Expr>(),
+            local_is_real.clone(),
+        );
+        builder.assert_eq(
+            local.y_sel.into_iter().map(|y| y.into()).sum::<AB::Expr>(),
+            local_is_real,
+        );
+        builder.assert_eq(
+            local.addr,
+            local
+                .y_sel
+                .into_iter()
+                .enumerate()
+                .map(|(y, sel)| sel * AB::Expr::from_canonical_usize(y * DISPLAY_WIDTH))
+                .sum::<AB::Expr>()
+                + local
+                    .x_sel
+                    .into_iter()
+                    .enumerate()
+                    .map(|(x, sel)| sel * AB::Expr::from_canonical_usize(x))
+                    .sum::<AB::Expr>(),
+        );
+
+        // Start and final values are exposed as public values
+        let select = |values: &[AB::PublicVar; FRAME_BUFFER_SIZE]| {
+            local
+                .y_sel
+                .into_iter()
+                .enumerate()
+                .map(|(y, y_sel)| {
+                    y_sel
+                        * local
+                            .x_sel
+                            .into_iter()
+                            .enumerate()
+                            .map(|(x, x_sel)| {
+                                let value: AB::Expr = values[y * DISPLAY_WIDTH + x].into();
+                                x_sel * value
+                            })
+                            .sum::<AB::Expr>()
+                })
+                .sum::<AB::Expr>()
+        };
+        builder
+            .when(local.is_init)
+            .assert_eq(local.value, select(&public_values.start_frame_buffer));
+        builder
+            .when(local.is_final)
+            .assert_eq(local.value, select(&public_values.end_frame_buffer));
     }
 }
@@ -1,16 +1,40 @@
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use p3_derive::Columnar;
 
 #[repr(C)]
-#[derive(Columnar, Default, Clone)]
+#[derive(Columnar, Clone)]
 pub struct FrameBufferCols<T> {
     pub addr: T,
     pub clk: T,
     pub value: T,
+    pub is_init: T,
     pub is_read: T,
     pub is_write: T,
     pub addr_unchanged: T,
     pub diff_limb_lo: T,
     pub diff_limb_hi: T,
-    pub is_first_read: T,
-    pub is_last_write: T,
+    pub is_final: T,
+
+    pub x_sel: [T; DISPLAY_WIDTH],
+    pub y_sel: [T; DISPLAY_HEIGHT],
+}
+
+// Arrays longer than 32 elements don't implement `Default`
+impl<T: Default + Copy> Default for FrameBufferCols<T> {
+    fn default() -> Self {
+        Self {
+            addr: T::default(),
+            clk: T::default(),
+            value: T::default(),
+            is_init: T::default(),
+            is_read: T::default(),
+            is_write: T::default(),
+            addr_unchanged: T::default(),
+            diff_limb_lo: T::default(),
+            diff_limb_hi: T::default(),
+            is_final: T::default(),
+            x_sel: [T::default(); DISPLAY_WIDTH],
+            y_sel: [T::default(); DISPLAY_HEIGHT],
+        }
+    }
 }
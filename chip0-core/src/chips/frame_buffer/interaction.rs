@@ -1,4 +1,4 @@
-use p3_air::VirtualPairCol;
+use p3_air::{AirBuilderWithPublicValues, VirtualPairCol};
 use p3_field::Field;
 use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
 
@@ -17,6 +17,7 @@ impl<F: Field> BaseInteractionAir<F> for FrameBufferChip {
                     VirtualPairCol::single_main(col_map.addr),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.value),
+                    VirtualPairCol::single_main(col_map.is_read),
                 ],
                 count: VirtualPairCol::sum_main(vec![col_map.is_read, col_map.is_write]),
                 argument_index: self.bus_frame_buffer,
@@ -42,4 +43,4 @@ impl<F: Field> InteractionAir<F> for FrameBufferChip {
     }
 }
 
-impl<AB: InteractionAirBuilder> Rap<AB> for FrameBufferChip {}
+impl<AB: InteractionAirBuilder + AirBuilderWithPublicValues> Rap<AB> for FrameBufferChip {}
@@ -1,23 +1,129 @@
 use core::borrow::Borrow;
-use p3_air::{Air, AirBuilder, BaseAir};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
+use p3_air_util::builders::SubAirBuilder;
 use p3_field::AbstractField;
+use p3_keccak_air::{KeccakAir, KeccakCols, BITS_PER_LIMB, NUM_KECCAK_COLS, NUM_ROUNDS, U64_LIMBS};
 use p3_matrix::Matrix;
 
+use crate::public_values::{PublicValues, INPUT_EVENT_LANES, KECCAK_STATE_SIZE};
+
 use super::columns::HashCols;
 use super::HashChip;
 
 impl<F> BaseAir<F> for HashChip {
     fn width(&self) -> usize {
-        HashCols::num_cols()
+        NUM_KECCAK_COLS + HashCols::<F>::num_cols()
     }
 }
 
-impl<AB: AirBuilder> Air<AB> for HashChip {
+impl<AB: AirBuilderWithPublicValues> Air<AB> for HashChip {
     fn eval(&self, builder: &mut AB) {
         let main = builder.main();
         let local = main.row_slice(0);
         let next = main.row_slice(1);
-        let local: &HashCols<AB::Var> = (*local).borrow();
-        let next: &HashCols<AB::Var> = (*next).borrow();
+        let (local_keccak, local) = local.split_at(NUM_KECCAK_COLS);
+        let (next_keccak, next) = next.split_at(NUM_KECCAK_COLS);
+        let local_keccak: &KeccakCols<AB::Var> = local_keccak.borrow();
+        let next_keccak: &KeccakCols<AB::Var> = next_keccak.borrow();
+        let local: &HashCols<AB::Var> = local.borrow();
+        let next: &HashCols<AB::Var> = next.borrow();
+
+        let public_values = builder.public_values().to_vec();
+        let public_values: &PublicValues<AB::PublicVar> = public_values.as_slice().borrow();
+
+        // Keccak-f permutation
+        let keccak_air = KeccakAir {};
+        let mut keccak_builder = SubAirBuilder::new_main(builder, (0..NUM_KECCAK_COLS).collect());
+        keccak_air.eval(&mut keccak_builder);
+
+        let is_first_step = local_keccak.step_flags[0];
+        let is_last_step = local_keccak.step_flags[NUM_ROUNDS - 1];
+
+        builder.assert_bool(local.is_real);
+        builder.assert_bool(local.is_absorb);
+        builder.assert_bool(local.is_final);
+
+        // Real permutations form a prefix of the trace
+        builder
+            .when_transition()
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(next.is_real);
+
+        // Event is fixed for all the rounds of a permutation
+        let mut builder_when_same_perm = builder.when_transition();
+        let mut builder_when_same_perm =
+            builder_when_same_perm.when_ne(is_last_step, AB::Expr::one());
+        builder_when_same_perm.assert_eq(next.is_real, local.is_real);
+        builder_when_same_perm.assert_eq(next.clk, local.clk);
+        builder_when_same_perm.assert_eq(next.key, local.key);
+        builder_when_same_perm.assert_eq(next.kind, local.kind);
+
+        // Each real permutation absorbs its event at the first round, into the first lanes
+        builder.assert_eq(local.is_absorb, local.is_real * is_first_step);
+        let event_lanes = [local.clk, local.key, local.kind];
+        for (x, value) in event_lanes.into_iter().enumerate() {
+            let lane = local_keccak.preimage[0][x];
+            builder.when(local.is_absorb).assert_eq(
+                lane[0] + lane[1] * AB::Expr::from_canonical_u32(1 << BITS_PER_LIMB),
+                value,
+            );
+            for limb in 2..U64_LIMBS {
+                builder.when(local.is_absorb).assert_zero(lane[limb]);
+            }
+        }
+
+        // Sponge starts from the start input state and every following permutation keeps the
+        // lanes that aren't absorbed
+        for lane in INPUT_EVENT_LANES..KECCAK_STATE_SIZE {
+            let (y, x) = (lane / 5, lane % 5);
+            for limb in 0..U64_LIMBS {
+                builder.when_first_row().when(local.is_real).assert_eq(
+                    local_keccak.preimage[y][x][limb],
+                    public_values.start_input_state[lane * U64_LIMBS + limb],
+                );
+            }
+        }
+        let mut builder_when_next_perm = builder.when_transition();
+        let mut builder_when_next_perm = builder_when_next_perm.when(is_last_step);
+        let mut builder_when_next_perm = builder_when_next_perm.when(next.is_real);
+        for lane in INPUT_EVENT_LANES..KECCAK_STATE_SIZE {
+            let (y, x) = (lane / 5, lane % 5);
+            for limb in 0..U64_LIMBS {
+                builder_when_next_perm.assert_eq(
+                    next_keccak.preimage[y][x][limb],
+                    local_keccak.a_prime_prime_prime(y, x, limb),
+                );
+            }
+        }
+
+        // Output of the last real permutation is the end input state, which is the start state
+        // when the segment has no events
+        builder.when(local.is_final).assert_one(local.is_real);
+        builder.when(local.is_final).assert_one(is_last_step);
+        builder
+            .when_transition()
+            .when(local.is_real)
+            .when(is_last_step)
+            .assert_eq(local.is_final, AB::Expr::one() - next.is_real);
+        builder
+            .when_last_row()
+            .assert_eq(local.is_final, local.is_real * is_last_step);
+        for lane in 0..KECCAK_STATE_SIZE {
+            let (y, x) = (lane / 5, lane % 5);
+            for limb in 0..U64_LIMBS {
+                let i = lane * U64_LIMBS + limb;
+                builder.when(local.is_final).assert_eq(
+                    local_keccak.a_prime_prime_prime(y, x, limb),
+                    public_values.end_input_state[i],
+                );
+                builder
+                    .when_first_row()
+                    .when_ne(local.is_real, AB::Expr::one())
+                    .assert_eq(
+                        public_values.end_input_state[i],
+                        public_values.start_input_state[i],
+                    );
+            }
+        }
     }
 }
@@ -1,7 +1,15 @@
 use p3_derive::Columnar;
 
+/// Columns following the Keccak permutation columns
 #[repr(C)]
 #[derive(Columnar, Default, Clone)]
 pub struct HashCols<T> {
+    // Each real permutation absorbs one event
     pub is_real: T,
+    pub is_absorb: T,
+    pub is_final: T,
+
+    pub clk: T,
+    pub key: T,
+    pub kind: T,
 }
@@ -1,12 +1,34 @@
-use itertools::Itertools;
-use p3_air::VirtualPairCol;
+use p3_air::{AirBuilderWithPublicValues, VirtualPairCol};
 use p3_field::Field;
 use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
+use p3_keccak_air::NUM_KECCAK_COLS;
 
-use super::{columns::HASH_COL_MAP, HashChip};
+use super::{columns::HashCols, HashChip};
 
-impl<F: Field> BaseInteractionAir<F> for HashChip {}
+impl<F: Field> BaseInteractionAir<F> for HashChip {
+    fn receives_from_indices(
+        &self,
+        _preprocessed_indices: &[usize],
+        main_indices: &[usize],
+    ) -> Vec<Interaction<F>> {
+        let col_map = HashCols::from_slice(&main_indices[NUM_KECCAK_COLS..]);
+        vec![Interaction {
+            fields: vec![
+                VirtualPairCol::single_main(col_map.clk),
+                VirtualPairCol::single_main(col_map.key),
+                VirtualPairCol::single_main(col_map.kind),
+            ],
+            count: VirtualPairCol::single_main(col_map.is_absorb),
+            argument_index: self.bus_hash,
+        }]
+    }
+}
 
-impl<F: Field> InteractionAir<F> for HashChip {}
+impl<F: Field> InteractionAir<F> for HashChip {
+    fn receives(&self) -> Vec<Interaction<F>> {
+        let main_indices = (0..NUM_KECCAK_COLS + HashCols::<F>::num_cols()).collect::<Vec<_>>();
+        self.receives_from_main_indices(&main_indices)
+    }
+}
 
-impl<AB: InteractionAirBuilder> Rap<AB> for HashChip {}
+impl<AB: InteractionAirBuilder + AirBuilderWithPublicValues> Rap<AB> for HashChip {}
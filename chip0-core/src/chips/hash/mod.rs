@@ -6,22 +6,29 @@ pub mod interaction;
 use p3_air_util::TraceWriter;
 #[cfg(feature = "trace-writer")]
 use p3_field::{ExtensionField, Field};
+#[cfg(feature = "trace-writer")]
+use p3_keccak_air::NUM_KECCAK_COLS;
 
 #[cfg(feature = "trace-writer")]
 use self::columns::HashCols;
 
 #[derive(Clone, Debug)]
-pub struct HashChip {}
+pub struct HashChip {
+    bus_hash: usize,
+}
 
 impl HashChip {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(bus_hash: usize) -> Self {
+        Self { bus_hash }
     }
 }
 
+#[cfg(feature = "trace-writer")]
 impl<F: Field, EF: ExtensionField<F>> TraceWriter<F, EF> for HashChip {
-    #[cfg(feature = "trace-writer")]
     fn main_headers(&self) -> Vec<String> {
-        HashCols::<F>::headers()
+        (0..NUM_KECCAK_COLS)
+            .map(|i| format!("keccak[{i}]"))
+            .chain(HashCols::<F>::headers())
+            .collect()
     }
 }
@@ -1,5 +1,6 @@
 use core::borrow::Borrow;
 use p3_air::{Air, AirBuilder, BaseAir};
+use p3_field::AbstractField;
 use p3_matrix::Matrix;
 
 use super::columns::KeypadCols;
@@ -18,5 +19,17 @@ impl<AB: AirBuilder> Air<AB> for KeypadChip {
         let next = main.row_slice(1);
         let local: &KeypadCols<AB::Var> = (*local).borrow();
         let next: &KeypadCols<AB::Var> = (*next).borrow();
+
+        builder.assert_bool(local.is_real);
+        builder.assert_bool(local.value);
+        builder
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(local.value);
+
+        // Real rows form a prefix of the trace
+        builder
+            .when_transition()
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(next.is_real);
     }
 }
@@ -7,6 +7,4 @@ pub struct KeypadCols<T> {
     pub clk: T,
     pub index: T,
     pub value: T,
-    pub input_hash: T,
-    pub output_hash: T,
 }
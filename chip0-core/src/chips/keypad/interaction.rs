@@ -12,15 +12,27 @@ impl<F: Field> BaseInteractionAir<F> for KeypadChip {
     ) -> Vec<Interaction<F>> {
         let col_map = KeypadCols::from_slice(main_indices);
         vec![
-            // Interaction {
-            //     fields: vec![
-            //         VirtualPairCol::single_main(col_map.clk),
-            //         VirtualPairCol::single_main(col_map.index),
-            //         VirtualPairCol::single_main(col_map.value),
-            //     ],
-            //     count: VirtualPairCol::single_main(col_map.is_real),
-            //     argument_index: self.bus_keypad,
-            // }
+            // A press adds one to the key state and a release subtracts one
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_main(col_map.clk),
+                    VirtualPairCol::single_main(col_map.index),
+                ],
+                count: VirtualPairCol::new_main(
+                    vec![(col_map.value, F::two()), (col_map.is_real, F::neg_one())],
+                    F::zero(),
+                ),
+                argument_index: self.bus_keypad,
+            },
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_main(col_map.clk),
+                    VirtualPairCol::single_main(col_map.index),
+                    VirtualPairCol::single_main(col_map.value),
+                ],
+                count: VirtualPairCol::single_main(col_map.is_real),
+                argument_index: self.bus_hash,
+            },
         ]
     }
 }
@@ -13,11 +13,15 @@ use self::columns::KeypadCols;
 #[derive(Clone, Debug)]
 pub struct KeypadChip {
     bus_keypad: usize,
+    bus_hash: usize,
 }
 
 impl KeypadChip {
-    pub fn new(bus_keypad: usize) -> Self {
-        Self { bus_keypad }
+    pub fn new(bus_keypad: usize, bus_hash: usize) -> Self {
+        Self {
+            bus_keypad,
+            bus_hash,
+        }
     }
 }
 
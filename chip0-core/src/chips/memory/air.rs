@@ -20,33 +20,68 @@ impl<AB: AirBuilder> Air<AB> for MemoryChip {
         let local: &MemoryCols<AB::Var> = (*local).borrow();
         let next: &MemoryCols<AB::Var> = (*next).borrow();
 
+        builder.assert_bool(local.is_init);
         builder.assert_bool(local.is_read);
         builder.assert_bool(local.is_write);
+        builder.assert_bool(local.is_final);
+
+        let local_is_real = local.is_init + local.is_read + local.is_write;
+        let next_is_real = next.is_init + next.is_read + next.is_write;
+        builder.assert_bool(local_is_real.clone());
+
+        // Real rows form a prefix of the trace
+        builder
+            .when_transition()
+            .when_ne(local_is_real.clone(), AB::Expr::one())
+            .assert_zero(next_is_real.clone());
+
+        // Every accessed address starts with its value at the start of the segment, which is
+        // received from the memory start chip
+        builder
+            .when_first_row()
+            .assert_eq(local.is_init, local_is_real.clone());
+        builder.when(local.is_init).assert_zero(local.clk);
 
-        builder.assert_zero(local.is_read * local.is_write);
         builder.assert_bool(local.addr_unchanged);
+        builder
+            .when_transition()
+            .when(next_is_real.clone())
+            .assert_eq(next.addr_unchanged, AB::Expr::one() - next.is_init);
 
         builder
             .when_transition()
             .when(next.addr_unchanged)
             .assert_eq(local.addr, next.addr);
 
+        // Addresses are strictly increasing so each one is initialized once
         let diff = next.diff_limb_lo + next.diff_limb_hi * AB::Expr::from_canonical_u32(1 << 8);
         builder
             .when_transition()
-            .when(next.addr_unchanged)
-            .assert_eq(diff.clone(), next.clk - local.clk);
+            .when(next.is_init)
+            .assert_eq(diff.clone(), next.addr - local.addr - AB::Expr::one());
         builder
             .when_transition()
-            .when(next.is_read + next.is_write)
-            .when_ne(next.addr_unchanged, AB::Expr::one())
-            .assert_eq(diff, next.addr - local.addr - AB::Expr::one());
+            .when(next.addr_unchanged)
+            .when_ne(local.is_init, AB::Expr::one())
+            .assert_eq(diff, next.clk - local.clk);
 
-        // TODO: Do I need this?
+        // Reads return the initial or last written value
         builder
             .when_transition()
             .when(next.addr_unchanged)
             .when(next.is_read)
             .assert_eq(local.value, next.value);
+
+        // The last access of every address is final and sent to the memory start chip
+        builder
+            .when_transition()
+            .when(local_is_real.clone())
+            .assert_eq(local.is_final, AB::Expr::one() - next.addr_unchanged);
+        builder
+            .when_last_row()
+            .assert_eq(local.is_final, local_is_real.clone());
+        builder
+            .when_ne(local_is_real, AB::Expr::one())
+            .assert_zero(local.is_final);
     }
 }
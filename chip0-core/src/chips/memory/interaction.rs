@@ -16,7 +16,7 @@ impl<F: Field> BaseInteractionAir<F> for MemoryChip {
                 VirtualPairCol::single_main(col_map.addr),
                 VirtualPairCol::single_main(col_map.value),
             ],
-            count: VirtualPairCol::single_main(col_map.is_first_read),
+            count: VirtualPairCol::single_main(col_map.is_init),
             argument_index: self.bus_memory_start,
         }]
     }
@@ -33,18 +33,35 @@ impl<F: Field> BaseInteractionAir<F> for MemoryChip {
                     VirtualPairCol::single_main(col_map.addr),
                     VirtualPairCol::single_main(col_map.clk),
                     VirtualPairCol::single_main(col_map.value),
+                    VirtualPairCol::single_main(col_map.is_read),
                 ],
                 count: VirtualPairCol::sum_main(vec![col_map.is_read, col_map.is_write]),
                 argument_index: self.bus_memory,
             },
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_main(col_map.addr),
+                    VirtualPairCol::single_main(col_map.value),
+                ],
+                count: VirtualPairCol::single_main(col_map.is_final),
+                argument_index: self.bus_memory_final,
+            },
             Interaction {
                 fields: vec![VirtualPairCol::single_main(col_map.diff_limb_lo)],
-                count: VirtualPairCol::sum_main(vec![col_map.is_read, col_map.is_write]),
+                count: VirtualPairCol::sum_main(vec![
+                    col_map.is_init,
+                    col_map.is_read,
+                    col_map.is_write,
+                ]),
                 argument_index: self.bus_range,
             },
             Interaction {
                 fields: vec![VirtualPairCol::single_main(col_map.diff_limb_hi)],
-                count: VirtualPairCol::sum_main(vec![col_map.is_read, col_map.is_write]),
+                count: VirtualPairCol::sum_main(vec![
+                    col_map.is_init,
+                    col_map.is_read,
+                    col_map.is_write,
+                ]),
                 argument_index: self.bus_range,
             },
         ]
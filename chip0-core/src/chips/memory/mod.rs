@@ -13,14 +13,21 @@ use self::columns::MemoryCols;
 #[derive(Clone, Debug)]
 pub struct MemoryChip {
     bus_memory_start: usize,
+    bus_memory_final: usize,
     bus_memory: usize,
     bus_range: usize,
 }
 
 impl MemoryChip {
-    pub fn new(bus_memory_start: usize, bus_memory: usize, bus_range: usize) -> Self {
+    pub fn new(
+        bus_memory_start: usize,
+        bus_memory_final: usize,
+        bus_memory: usize,
+        bus_range: usize,
+    ) -> Self {
         Self {
             bus_memory_start,
+            bus_memory_final,
             bus_memory,
             bus_range,
         }
@@ -0,0 +1,130 @@
+use chip8_core::constants::MEMORY_SIZE;
+use core::borrow::Borrow;
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
+use p3_air_util::builders::SubAirBuilder;
+use p3_field::AbstractField;
+use p3_keccak_air::{KeccakAir, KeccakCols, NUM_KECCAK_COLS, NUM_ROUNDS, U64_LIMBS};
+use p3_matrix::Matrix;
+
+use crate::public_values::{PublicValues, DIGEST_SIZE, LANE_BYTES, MEMORY_CHUNK_LANES};
+
+use super::columns::MemoryFinalCols;
+use super::MemoryFinalChip;
+
+const DIGEST_LANES: usize = DIGEST_SIZE / U64_LIMBS;
+
+impl<F> BaseAir<F> for MemoryFinalChip {
+    fn width(&self) -> usize {
+        NUM_KECCAK_COLS + MemoryFinalCols::<F>::num_cols()
+    }
+}
+
+impl<AB: AirBuilderWithPublicValues> Air<AB> for MemoryFinalChip {
+    fn eval(&self, builder: &mut AB) {
+        let main = builder.main();
+        let local = main.row_slice(0);
+        let next = main.row_slice(1);
+        let (local_keccak, local) = local.split_at(NUM_KECCAK_COLS);
+        let (next_keccak, next) = next.split_at(NUM_KECCAK_COLS);
+        let local_keccak: &KeccakCols<AB::Var> = local_keccak.borrow();
+        let next_keccak: &KeccakCols<AB::Var> = next_keccak.borrow();
+        let local: &MemoryFinalCols<AB::Var> = local.borrow();
+        let next: &MemoryFinalCols<AB::Var> = next.borrow();
+
+        let public_values = builder.public_values().to_vec();
+        let public_values: &PublicValues<AB::PublicVar> = public_values.as_slice().borrow();
+
+        // Keccak-f permutation
+        let keccak_air = KeccakAir {};
+        let mut keccak_builder = SubAirBuilder::new_main(builder, (0..NUM_KECCAK_COLS).collect());
+        keccak_air.eval(&mut keccak_builder);
+
+        let is_last_step = local_keccak.step_flags[NUM_ROUNDS - 1];
+        let is_absorb_step = local_keccak.step_flags[..MEMORY_CHUNK_LANES]
+            .iter()
+            .map(|&flag| flag.into())
+            .sum::<AB::Expr>();
+
+        builder.assert_bool(local.is_real);
+        builder.assert_bool(local.is_absorb);
+        builder.assert_bool(local.is_digest);
+
+        // Real permutations form a prefix of the trace
+        builder
+            .when_transition()
+            .when_ne(local.is_real, AB::Expr::one())
+            .assert_zero(next.is_real);
+        builder
+            .when_transition()
+            .when_ne(is_last_step, AB::Expr::one())
+            .assert_eq(next.is_real, local.is_real);
+
+        // The first rounds of each permutation absorb one lane each, from consecutive addresses
+        builder.assert_eq(local.is_absorb, local.is_real * is_absorb_step);
+        for (lane, &flag) in local_keccak.step_flags[..MEMORY_CHUNK_LANES]
+            .iter()
+            .enumerate()
+        {
+            let preimage = local_keccak.preimage[lane / 5][lane % 5];
+            for limb in 0..U64_LIMBS {
+                builder.when(local.is_absorb).when(flag).assert_eq(
+                    preimage[limb],
+                    local.values[2 * limb]
+                        + local.values[2 * limb + 1] * AB::Expr::from_canonical_u32(1 << 8),
+                );
+            }
+        }
+        builder.when_transition().when(next.is_real).assert_eq(
+            next.addr,
+            local.addr + local.is_absorb * AB::Expr::from_canonical_usize(LANE_BYTES),
+        );
+
+        // Sponge starts from the zero state and the first address
+        builder.when_first_row().assert_one(local.is_real);
+        builder.when_first_row().assert_zero(local.addr);
+        for lane in MEMORY_CHUNK_LANES..5 * 5 {
+            for limb in 0..U64_LIMBS {
+                builder
+                    .when_first_row()
+                    .assert_zero(local_keccak.preimage[lane / 5][lane % 5][limb]);
+            }
+        }
+
+        // Every following permutation keeps the lanes that aren't absorbed
+        let mut builder_when_next_perm = builder.when_transition();
+        let mut builder_when_next_perm = builder_when_next_perm.when(is_last_step);
+        let mut builder_when_next_perm = builder_when_next_perm.when(next.is_real);
+        for lane in MEMORY_CHUNK_LANES..5 * 5 {
+            let (y, x) = (lane / 5, lane % 5);
+            for limb in 0..U64_LIMBS {
+                builder_when_next_perm.assert_eq(
+                    next_keccak.preimage[y][x][limb],
+                    local_keccak.a_prime_prime_prime(y, x, limb),
+                );
+            }
+        }
+
+        // Output of the last real permutation, after the whole memory is absorbed, is the digest
+        builder.when(local.is_digest).assert_one(local.is_real);
+        builder.when(local.is_digest).assert_one(is_last_step);
+        builder
+            .when_transition()
+            .when(local.is_real)
+            .when(is_last_step)
+            .assert_eq(local.is_digest, AB::Expr::one() - next.is_real);
+        builder
+            .when_last_row()
+            .assert_eq(local.is_digest, local.is_real * is_last_step);
+        builder
+            .when(local.is_digest)
+            .assert_eq(local.addr, AB::Expr::from_canonical_usize(MEMORY_SIZE));
+        for x in 0..DIGEST_LANES {
+            for limb in 0..U64_LIMBS {
+                builder.when(local.is_digest).assert_eq(
+                    local_keccak.a_prime_prime_prime(0, x, limb),
+                    public_values.end_memory_digest[x * U64_LIMBS + limb],
+                );
+            }
+        }
+    }
+}
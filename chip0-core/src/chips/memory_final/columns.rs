@@ -0,0 +1,17 @@
+use p3_derive::Columnar;
+
+use crate::public_values::LANE_BYTES;
+
+/// Columns following the Keccak permutation columns
+#[repr(C)]
+#[derive(Columnar, Default, Clone)]
+pub struct MemoryFinalCols<T> {
+    pub is_real: T,
+    // One lane of the preimage is absorbed in each of the first rounds of a permutation
+    pub is_absorb: T,
+    pub is_digest: T,
+
+    /// Address of the first byte of the absorbed lane
+    pub addr: T,
+    pub values: [T; LANE_BYTES],
+}
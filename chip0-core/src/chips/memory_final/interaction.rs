@@ -0,0 +1,42 @@
+use p3_air::{AirBuilderWithPublicValues, VirtualPairCol};
+use p3_field::Field;
+use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
+use p3_keccak_air::NUM_KECCAK_COLS;
+
+use super::{columns::MemoryFinalCols, MemoryFinalChip};
+
+impl<F: Field> BaseInteractionAir<F> for MemoryFinalChip {
+    fn receives_from_indices(
+        &self,
+        _preprocessed_indices: &[usize],
+        main_indices: &[usize],
+    ) -> Vec<Interaction<F>> {
+        let col_map = MemoryFinalCols::from_slice(&main_indices[NUM_KECCAK_COLS..]);
+        col_map
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| Interaction {
+                fields: vec![
+                    VirtualPairCol::new_main(
+                        vec![(col_map.addr, F::one())],
+                        F::from_canonical_usize(i),
+                    ),
+                    VirtualPairCol::single_main(value),
+                ],
+                count: VirtualPairCol::single_main(col_map.is_absorb),
+                argument_index: self.bus_memory_final,
+            })
+            .collect()
+    }
+}
+
+impl<F: Field> InteractionAir<F> for MemoryFinalChip {
+    fn receives(&self) -> Vec<Interaction<F>> {
+        let main_indices =
+            (0..NUM_KECCAK_COLS + MemoryFinalCols::<F>::num_cols()).collect::<Vec<_>>();
+        self.receives_from_main_indices(&main_indices)
+    }
+}
+
+impl<AB: InteractionAirBuilder + AirBuilderWithPublicValues> Rap<AB> for MemoryFinalChip {}
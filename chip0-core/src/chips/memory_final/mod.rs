@@ -0,0 +1,35 @@
+pub mod air;
+pub mod columns;
+pub mod interaction;
+
+#[cfg(feature = "trace-writer")]
+use p3_air_util::TraceWriter;
+#[cfg(feature = "trace-writer")]
+use p3_field::{ExtensionField, Field};
+#[cfg(feature = "trace-writer")]
+use p3_keccak_air::NUM_KECCAK_COLS;
+
+#[cfg(feature = "trace-writer")]
+use self::columns::MemoryFinalCols;
+
+/// Hashes the memory image at the end of the segment into the end memory digest.
+#[derive(Clone, Debug)]
+pub struct MemoryFinalChip {
+    bus_memory_final: usize,
+}
+
+impl MemoryFinalChip {
+    pub fn new(bus_memory_final: usize) -> Self {
+        Self { bus_memory_final }
+    }
+}
+
+#[cfg(feature = "trace-writer")]
+impl<F: Field, EF: ExtensionField<F>> TraceWriter<F, EF> for MemoryFinalChip {
+    fn main_headers(&self) -> Vec<String> {
+        (0..NUM_KECCAK_COLS)
+            .map(|i| format!("keccak[{i}]"))
+            .chain(MemoryFinalCols::<F>::headers())
+            .collect()
+    }
+}
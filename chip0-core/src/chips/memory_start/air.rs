@@ -1,4 +1,4 @@
-use chip8_core::constants::{FONTSET, FONTSET_START_ADDRESS, MEMORY_SIZE, PROGRAM_START_ADDRESS};
+use chip8_core::constants::MEMORY_SIZE;
 use core::borrow::Borrow;
 use p3_air::{Air, AirBuilder, BaseAir};
 use p3_field::Field;
@@ -33,18 +33,7 @@ impl<F: Field> BaseAir<F> for MemoryStartChip {
 
         for i in 0..MEMORY_SIZE {
             rows[i].addr = F::from_canonical_usize(i);
-        }
-
-        let start = FONTSET_START_ADDRESS as usize;
-        let end = FONTSET_START_ADDRESS as usize + FONTSET.len();
-        for i in start..end {
-            rows[i].value = F::from_canonical_u8(FONTSET[i - start]);
-        }
-
-        let start = PROGRAM_START_ADDRESS as usize;
-        let end = PROGRAM_START_ADDRESS as usize + self.rom.len();
-        for i in start..end {
-            rows[i].value = F::from_canonical_u8(self.rom[i - start]);
+            rows[i].value = F::from_canonical_u8(self.memory[i]);
         }
 
         Some(trace)
@@ -55,8 +44,9 @@ impl<AB: AirBuilder> Air<AB> for MemoryStartChip {
     fn eval(&self, builder: &mut AB) {
         let main = builder.main();
         let local = main.row_slice(0);
-        let next = main.row_slice(1);
         let local: &MemoryStartCols<AB::Var> = (*local).borrow();
-        let next: &MemoryStartCols<AB::Var> = (*next).borrow();
+
+        // Each address is initialized at most once by the memory chip
+        builder.assert_bool(local.mult);
     }
 }
@@ -10,5 +10,6 @@ pub struct MemoryStartPreprocessedCols<T> {
 #[repr(C)]
 #[derive(Columnar, Default, Clone)]
 pub struct MemoryStartCols<T> {
+    /// Whether the address is accessed in the segment
     pub mult: T,
 }
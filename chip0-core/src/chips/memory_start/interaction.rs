@@ -15,14 +15,25 @@ impl<F: Field> BaseInteractionAir<F> for MemoryStartChip {
     ) -> Vec<Interaction<F>> {
         let preprocessed_col_map = MemoryStartPreprocessedCols::from_slice(preprocessed_indices);
         let col_map = MemoryStartCols::from_slice(main_indices);
-        vec![Interaction {
-            fields: vec![
-                VirtualPairCol::single_preprocessed(preprocessed_col_map.addr),
-                VirtualPairCol::single_preprocessed(preprocessed_col_map.value),
-            ],
-            count: VirtualPairCol::single_main(col_map.mult),
-            argument_index: self.bus_memory_start,
-        }]
+        vec![
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_preprocessed(preprocessed_col_map.addr),
+                    VirtualPairCol::single_preprocessed(preprocessed_col_map.value),
+                ],
+                count: VirtualPairCol::single_main(col_map.mult),
+                argument_index: self.bus_memory_start,
+            },
+            // Addresses that aren't accessed keep their start value
+            Interaction {
+                fields: vec![
+                    VirtualPairCol::single_preprocessed(preprocessed_col_map.addr),
+                    VirtualPairCol::single_preprocessed(preprocessed_col_map.value),
+                ],
+                count: VirtualPairCol::new_main(vec![(col_map.mult, -F::one())], F::one()),
+                argument_index: self.bus_memory_final,
+            },
+        ]
     }
 }
 
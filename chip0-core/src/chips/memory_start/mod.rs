@@ -12,15 +12,18 @@ use self::columns::{MemoryStartCols, MemoryStartPreprocessedCols};
 
 #[derive(Clone, Debug)]
 pub struct MemoryStartChip {
-    rom: Vec<u8>,
+    /// Memory image at the start of the segment
+    memory: Vec<u8>,
     bus_memory_start: usize,
+    bus_memory_final: usize,
 }
 
 impl MemoryStartChip {
-    pub fn new(rom: Vec<u8>, bus_memory_start: usize) -> Self {
+    pub fn new(memory: Vec<u8>, bus_memory_start: usize, bus_memory_final: usize) -> Self {
         Self {
-            rom,
+            memory,
             bus_memory_start,
+            bus_memory_final,
         }
     }
 }
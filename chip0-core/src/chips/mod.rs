@@ -5,17 +5,40 @@ pub mod clear;
 pub mod cpu;
 pub mod draw;
 pub mod frame_buffer;
-// pub mod hash;
+pub mod hash;
 pub mod keypad;
 pub mod memory;
+pub mod memory_final;
 pub mod memory_start;
+pub mod random;
 pub mod range;
 
 use self::{
-    clear::ClearChip, cpu::CpuChip, draw::DrawChip, frame_buffer::FrameBufferChip,
-    keypad::KeypadChip, memory::MemoryChip, memory_start::MemoryStartChip, range::RangeChip,
+    clear::ClearChip, cpu::CpuChip, draw::DrawChip, frame_buffer::FrameBufferChip, hash::HashChip,
+    keypad::KeypadChip, memory::MemoryChip, memory_final::MemoryFinalChip,
+    memory_start::MemoryStartChip, random::RandomChip, range::RangeChip,
 };
 
+/// Runs `$body` with `$inner` bound to the chip wrapped by `$chip`.
+macro_rules! with_chip {
+    ($chip:expr, $inner:ident => $body:expr) => {
+        match $chip {
+            $crate::chips::Chip0MachineChip::Cpu($inner) => $body,
+            $crate::chips::Chip0MachineChip::Clear($inner) => $body,
+            $crate::chips::Chip0MachineChip::Draw($inner) => $body,
+            $crate::chips::Chip0MachineChip::Keypad($inner) => $body,
+            $crate::chips::Chip0MachineChip::Memory($inner) => $body,
+            $crate::chips::Chip0MachineChip::FrameBuffer($inner) => $body,
+            $crate::chips::Chip0MachineChip::Range($inner) => $body,
+            $crate::chips::Chip0MachineChip::MemoryStart($inner) => $body,
+            $crate::chips::Chip0MachineChip::MemoryFinal($inner) => $body,
+            $crate::chips::Chip0MachineChip::Hash($inner) => $body,
+            $crate::chips::Chip0MachineChip::Random($inner) => $body,
+        }
+    };
+}
+pub(crate) use with_chip;
+
 #[derive(Clone, Debug, EnumDispatch)]
 pub enum Chip0MachineChip {
     Cpu(CpuChip),
@@ -26,4 +49,26 @@ pub enum Chip0MachineChip {
     FrameBuffer(FrameBufferChip),
     Range(RangeChip),
     MemoryStart(MemoryStartChip),
+    MemoryFinal(MemoryFinalChip),
+    Hash(HashChip),
+    Random(RandomChip),
+}
+
+impl Chip0MachineChip {
+    /// Short name of the chip, used in diagnostics and exported trace files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cpu(_) => "cpu",
+            Self::Clear(_) => "clear",
+            Self::Draw(_) => "draw",
+            Self::Keypad(_) => "keypad",
+            Self::Memory(_) => "memory",
+            Self::FrameBuffer(_) => "frame_buffer",
+            Self::Range(_) => "range",
+            Self::MemoryStart(_) => "memory_start",
+            Self::MemoryFinal(_) => "memory_final",
+            Self::Hash(_) => "hash",
+            Self::Random(_) => "random",
+        }
+    }
 }
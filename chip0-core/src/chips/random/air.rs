@@ -0,0 +1,59 @@
+use chip8_core::rng::{LFSR_SHIFTS_PER_BYTE, LFSR_TAPS};
+use core::borrow::Borrow;
+use p3_air::{Air, AirBuilder, BaseAir};
+use p3_field::AbstractField;
+use p3_matrix::Matrix;
+
+use crate::chips::draw::columns::WORD_BITS;
+
+use super::columns::RandomCols;
+use super::RandomChip;
+
+impl<F> BaseAir<F> for RandomChip {
+    fn width(&self) -> usize {
+        RandomCols::<F>::num_cols()
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for RandomChip {
+    fn eval(&self, builder: &mut AB) {
+        let main = builder.main();
+        let local = main.row_slice(0);
+        let local: &RandomCols<AB::Var> = (*local).borrow();
+
+        builder.assert_bool(local.is_real);
+        for bit in local.state_bits {
+            builder.assert_bool(bit);
+        }
+        for bit in local.nn_bits {
+            builder.assert_bool(bit);
+        }
+
+        // The i-th shift XORs the taps offset by i, which all lie in the initial state
+        for i in 0..LFSR_SHIFTS_PER_BYTE {
+            let feedback = local.feedback_bits[i];
+            let carry = local.feedback_carry[i];
+            builder.assert_bool(feedback);
+            builder.assert_zero(carry * (carry - AB::Expr::one()) * (carry - AB::Expr::two()));
+            builder.assert_eq(
+                LFSR_TAPS
+                    .iter()
+                    .map(|&tap| local.state_bits[i + tap].into())
+                    .sum::<AB::Expr>(),
+                feedback + carry * AB::Expr::two(),
+            );
+        }
+
+        // The byte is the low byte of the new state, which is the high byte of the old one
+        builder.assert_eq(
+            local.value,
+            (0..WORD_BITS)
+                .map(|i| {
+                    local.state_bits[LFSR_SHIFTS_PER_BYTE + i]
+                        * local.nn_bits[i]
+                        * AB::Expr::from_canonical_u32(1 << i)
+                })
+                .sum::<AB::Expr>(),
+        );
+    }
+}
@@ -0,0 +1,24 @@
+use chip8_core::rng::{LFSR_BITS, LFSR_SHIFTS_PER_BYTE};
+use p3_derive::Columnar;
+
+use crate::chips::draw::columns::WORD_BITS;
+
+// Each step shifts out the low byte and its value is the high byte of the previous state
+const _: () = assert!(LFSR_BITS == 2 * LFSR_SHIFTS_PER_BYTE);
+
+#[repr(C)]
+#[derive(Columnar, Default, Clone)]
+pub struct RandomCols<T> {
+    pub is_real: T,
+
+    // LFSR state before the step
+    pub state_bits: [T; LFSR_BITS],
+    // Bits shifted in, which end up in the high byte of the state
+    pub feedback_bits: [T; LFSR_SHIFTS_PER_BYTE],
+    // Sum of the taps is feedback + 2 * carry
+    pub feedback_carry: [T; LFSR_SHIFTS_PER_BYTE],
+
+    pub nn_bits: [T; WORD_BITS],
+    // Low byte of the new state masked with nn
+    pub value: T,
+}
@@ -0,0 +1,57 @@
+use chip8_core::rng::LFSR_SHIFTS_PER_BYTE;
+use p3_air::VirtualPairCol;
+use p3_field::Field;
+use p3_interaction::{BaseInteractionAir, Interaction, InteractionAir, InteractionAirBuilder, Rap};
+
+use super::{columns::RandomCols, RandomChip};
+
+impl<F: Field> BaseInteractionAir<F> for RandomChip {
+    fn receives_from_indices(
+        &self,
+        _preprocessed_indices: &[usize],
+        main_indices: &[usize],
+    ) -> Vec<Interaction<F>> {
+        let col_map = RandomCols::from_slice(main_indices);
+        let power = |i: usize| F::from_canonical_u32(1 << i);
+
+        let state = col_map
+            .state_bits
+            .into_iter()
+            .enumerate()
+            .map(|(i, bit)| (bit, power(i)))
+            .collect();
+        // Each shift moves the state right by one bit and inserts the feedback at the top
+        let next_state = col_map.state_bits[LFSR_SHIFTS_PER_BYTE..]
+            .iter()
+            .chain(col_map.feedback_bits.iter())
+            .enumerate()
+            .map(|(i, &bit)| (bit, power(i)))
+            .collect();
+        let nn = col_map
+            .nn_bits
+            .into_iter()
+            .enumerate()
+            .map(|(i, bit)| (bit, power(i)))
+            .collect();
+
+        vec![Interaction {
+            fields: vec![
+                VirtualPairCol::new_main(state, F::zero()),
+                VirtualPairCol::new_main(next_state, F::zero()),
+                VirtualPairCol::new_main(nn, F::zero()),
+                VirtualPairCol::single_main(col_map.value),
+            ],
+            count: VirtualPairCol::single_main(col_map.is_real),
+            argument_index: self.bus_random,
+        }]
+    }
+}
+
+impl<F: Field> InteractionAir<F> for RandomChip {
+    fn receives(&self) -> Vec<Interaction<F>> {
+        let col_map = RandomCols::<F>::col_map();
+        self.receives_from_main_indices(col_map.as_slice())
+    }
+}
+
+impl<AB: InteractionAirBuilder> Rap<AB> for RandomChip {}
@@ -0,0 +1,30 @@
+pub mod air;
+pub mod columns;
+pub mod interaction;
+
+#[cfg(feature = "trace-writer")]
+use p3_air_util::TraceWriter;
+#[cfg(feature = "trace-writer")]
+use p3_field::{ExtensionField, Field};
+
+#[cfg(feature = "trace-writer")]
+use self::columns::RandomCols;
+
+/// Steps of the CXNN generator, see [`chip8_core::rng::Lfsr`].
+#[derive(Clone, Debug)]
+pub struct RandomChip {
+    bus_random: usize,
+}
+
+impl RandomChip {
+    pub fn new(bus_random: usize) -> Self {
+        Self { bus_random }
+    }
+}
+
+#[cfg(feature = "trace-writer")]
+impl<F: Field, EF: ExtensionField<F>> TraceWriter<F, EF> for RandomChip {
+    fn main_headers(&self) -> Vec<String> {
+        RandomCols::<F>::headers()
+    }
+}
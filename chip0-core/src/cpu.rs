@@ -4,56 +4,70 @@ use chip8_core::{
     error::Chip8Error,
     input::{InputEvent, InputQueue},
     instruction::Instruction,
+    quirks::Quirks,
+    rng::Lfsr,
     rwlock::CheckedWrite,
     state::{State, Word},
     util::run_loop,
 };
 use p3_field::{AbstractField, PrimeField32};
 use p3_uni_stark::{StarkGenericConfig, Val};
-use rand::Rng;
 use std::{
     collections::VecDeque,
+    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
-use crate::{chips::draw::columns::WORD_BITS, prover::Prover, trace::StarkState};
+use crate::{
+    chips::draw::columns::WORD_BITS, error::Chip0Error, proof::SessionProof, prover::Prover,
+    trace::StarkState,
+};
 
 pub const TICKS_PER_PROOF: u64 = 10000;
 
-pub struct StarkCpu<R, SC, P>
+// Segments must start on a timer tick
+const _: () = assert!(TICKS_PER_PROOF % TICKS_PER_TIMER == 0);
+
+pub struct StarkCpu<SC, P>
 where
-    R: Rng,
     SC: StarkGenericConfig,
     Val<SC>: PrimeField32,
     P: Prover<SC> + Send + Sync + 'static,
 {
     state: StarkState<Val<SC>>,
     clk_freq: u64,
-    rng: R,
+    rng: Lfsr,
 
     prover: Arc<P>,
+    proof_path: Option<PathBuf>,
 }
 
-impl<R, SC, P> StarkCpu<R, SC, P>
+impl<SC, P> StarkCpu<SC, P>
 where
-    R: Rng,
     SC: StarkGenericConfig,
     Val<SC>: PrimeField32,
     P: Prover<SC> + Send + Sync + 'static,
 {
-    pub fn new(clk_freq: u64, rng: R, prover: P) -> Self {
+    /// CXNN draws from an [`Lfsr`] seeded with `random_seed`, which is committed in the public
+    /// values of the first segment.
+    pub fn new(clk_freq: u64, random_seed: u16, prover: P, proof_path: Option<PathBuf>) -> Self {
         Self {
-            state: StarkState::default(),
+            state: StarkState::new(random_seed),
             clk_freq,
-            rng,
+            rng: Lfsr::new(random_seed),
             prover: Arc::new(prover),
+            proof_path,
         }
     }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.state.quirks = quirks;
+        self
+    }
 }
 
-impl<R, SC, P> Cpu for StarkCpu<R, SC, P>
+impl<SC, P> Cpu for StarkCpu<SC, P>
 where
-    R: Rng,
     SC: StarkGenericConfig,
     Val<SC>: PrimeField32,
     P: Prover<SC> + Send + Sync + 'static,
@@ -65,14 +79,25 @@ where
     }
 
     fn random(&mut self) -> Word {
-        self.rng.gen()
+        let value = self.rng.next_byte();
+        self.state.trace.cpu.curr_row.random_state =
+            Val::<SC>::from_canonical_u16(self.rng.state());
+        value
     }
 
     fn frequency(&self) -> u64 {
         self.clk_freq
     }
 
+    fn quirks(&self) -> Quirks {
+        self.state.quirks
+    }
+
     fn op_draw(&mut self, x: Word, y: Word, n: Word) -> Result<(), Chip8Error> {
+        if self.wait_for_display()? {
+            return Ok(());
+        }
+
         let clk = self.state().clk()?;
 
         let vx = self.state().register(x);
@@ -81,6 +106,7 @@ where
 
         let x0 = vx as usize % DISPLAY_WIDTH;
         let y0 = vy as usize % DISPLAY_HEIGHT;
+        let clip = self.quirks().clip;
         let mut flipped = false;
 
         // Each row in loop
@@ -91,29 +117,45 @@ where
         curr_row.register_x = Val::<SC>::from_canonical_u8(vx);
         curr_row.register_y = Val::<SC>::from_canonical_u8(vy);
         curr_row.index_register = Val::<SC>::from_canonical_u16(vi);
+        curr_row.n = Val::<SC>::from_canonical_u8(n);
+        for i in 0..WORD_BITS {
+            curr_row.register_x_bits[i] = Val::<SC>::from_canonical_u8((vx >> i) & 1);
+            curr_row.register_y_bits[i] = Val::<SC>::from_canonical_u8((vy >> i) & 1);
+        }
 
         for ys in 0..n {
             self.state().trace.draw.curr_row.is_first_inner = Val::<SC>::one();
 
+            let is_y_wrapped = y0 + ys as usize >= DISPLAY_HEIGHT;
             let y = (y0 + ys as usize) % DISPLAY_HEIGHT;
             let pixels = self.state().memory(vi + ys as u16)?;
             for xs in 0..8u8 {
+                let is_x_wrapped = x0 + xs as usize >= DISPLAY_WIDTH;
                 let x = (x0 + xs as usize) % DISPLAY_WIDTH;
+                let is_visible = !clip || !(is_x_wrapped || is_y_wrapped);
                 let pixel = (pixels >> (7 - xs)) & 1 == 1;
-                let fb = self.state().frame_buffer(y, x)?;
-                let curr_flipped = pixel & fb;
+                let is_flip = pixel && is_visible;
+                let mut fb = false;
+                if is_visible {
+                    fb = self.state().frame_buffer(0, y, x)?;
+                }
+                let curr_flipped = is_flip & fb;
                 flipped |= curr_flipped;
-                if pixel {
-                    self.state().set_frame_buffer(y, x, !fb)?;
+                if is_flip {
+                    self.state().set_frame_buffer(0, y, x, !fb)?;
                 }
 
                 let curr_row = &mut self.state().trace.draw.curr_row;
                 curr_row.ys = Val::<SC>::from_canonical_u8(ys);
                 curr_row.y = Val::<SC>::from_canonical_usize(y);
+                curr_row.is_y_wrapped = Val::<SC>::from_bool(is_y_wrapped);
                 curr_row.pixels = Val::<SC>::from_canonical_u8(pixels);
                 curr_row.xs = Val::<SC>::from_canonical_u8(xs);
                 curr_row.x = Val::<SC>::from_canonical_usize(x);
+                curr_row.is_x_wrapped = Val::<SC>::from_bool(is_x_wrapped);
                 curr_row.pixel = Val::<SC>::from_bool(pixel);
+                curr_row.is_visible = Val::<SC>::from_bool(is_visible);
+                curr_row.is_flip = Val::<SC>::from_bool(is_flip);
                 curr_row.frame_buffer_y_x = Val::<SC>::from_bool(fb);
                 curr_row.flipped = Val::<SC>::from_bool(curr_flipped);
                 curr_row.register_flag = Val::<SC>::from_bool(flipped);
@@ -161,6 +203,7 @@ where
         let nn = (opcode & 0x00FF) as u8;
         let nnn = opcode & 0x0FFF;
 
+        curr_row.opcode_top = Val::<SC>::from_canonical_u16(opcode >> 12);
         curr_row.x = Val::<SC>::from_canonical_u8(x);
         curr_row.y = Val::<SC>::from_canonical_u8(y);
 
@@ -173,7 +216,9 @@ where
         curr_row.nn = Val::<SC>::from_canonical_u8(nn);
         curr_row.nnn = Val::<SC>::from_canonical_u16(nnn);
 
-        // TODO: Constraints for this
+        self.state().trace.cpu.set_operands();
+        let curr_row = &mut self.state().trace.cpu.curr_row;
+
         match opcode & 0xF000 {
             0x0000 => match opcode & 0xF0FF {
                 // 0x00E0
@@ -224,6 +269,8 @@ where
             // 0x7XNN
             0x7000 => {
                 curr_row.is_add = Val::<SC>::one();
+                curr_row.alu_flag =
+                    Val::<SC>::from_bool(curr_row.vx.as_canonical_u32() + nn as u32 > 0xFF);
                 Ok(Instruction::Add(x, nn))
             }
             0x8000 => match opcode & 0xF00F {
@@ -260,7 +307,7 @@ where
                 // 0x8XY6
                 0x8006 => {
                     curr_row.is_shift_right = Val::<SC>::one();
-                    Ok(Instruction::ShiftRight(x))
+                    Ok(Instruction::ShiftRight(x, y))
                 }
                 // 0x8XY7
                 0x8007 => {
@@ -270,7 +317,7 @@ where
                 // 0x8XYE
                 0x800E => {
                     curr_row.is_shift_left = Val::<SC>::one();
-                    Ok(Instruction::ShiftLeft(x))
+                    Ok(Instruction::ShiftLeft(x, y))
                 }
                 _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
             },
@@ -373,13 +420,32 @@ where
         }
     }
 
+    fn step(&mut self, input_queue: &mut impl InputQueue) -> Result<(), Chip8Error> {
+        let clk = self.state().clk()?;
+        let curr_row = &mut self.state().trace.cpu.curr_row;
+        if clk == 0 {
+            curr_row.is_first = Val::<SC>::one();
+        }
+        curr_row.is_real = Val::<SC>::one();
+
+        while let Some(event) = input_queue.dequeue(clk) {
+            self.state().set_key(event.key, event.kind);
+        }
+        self.tick()?;
+        if clk % TICKS_PER_TIMER == 0 {
+            self.tick_timers()?;
+        }
+
+        self.state().increment_clk()
+    }
+
     async fn run(
         &mut self,
         num_cycles: Option<u64>,
         status: Arc<RwLock<Result<(), Chip8Error>>>,
         input_queue: Arc<RwLock<VecDeque<(u64, InputEvent)>>>,
     ) {
-        // let mut prover_handle = None;
+        let mut prover_handles = Vec::new();
         run_loop(status.clone(), self.frequency(), |_| {
             let clk = self.state().clk()?;
             if let Some(num_cycles) = num_cycles {
@@ -388,28 +454,55 @@ where
                 }
             }
 
-            let curr_row = &mut self.state().trace.cpu.curr_row;
-            if clk == 0 {
-                curr_row.is_first = Val::<SC>::one();
-            }
-            curr_row.is_real = Val::<SC>::one();
+            self.step(&mut *input_queue.checked_write()?)?;
 
-            while let Some(event) = (*input_queue.checked_write()?).dequeue(clk) {
-                self.state().set_key(event.key, event.kind);
+            // Prove the finished segment in the background while execution continues
+            if (clk + 1) % TICKS_PER_PROOF == 0 {
+                let trace = self.state().finalize_trace()?;
+                let prover = self.prover.clone();
+                prover_handles.push(tokio::task::spawn_blocking(move || prover.prove(trace)));
             }
-            // TODO: How do I remove this clone?
-            self.tick(status.clone(), input_queue.clone())?;
-            if clk % TICKS_PER_TIMER == 0 {
-                self.tick_timers()?;
-            }
-
-            self.state().increment_clk()?;
 
             Ok(())
         });
 
-        let trace = self.state.finalize_trace();
-        let prover = self.prover.clone();
-        tokio::spawn(async move { prover.prove(trace) });
+        if !self.state.is_segment_empty() {
+            match self.state.finalize_trace() {
+                Ok(trace) => {
+                    let prover = self.prover.clone();
+                    prover_handles.push(tokio::task::spawn_blocking(move || prover.prove(trace)));
+                }
+                Err(err) => tracing::error!("Failed to finalize trace: {err}"),
+            }
+        }
+
+        // Segments proven before a failure still form a valid session, so they are saved
+        let mut segments = Vec::with_capacity(prover_handles.len());
+        let mut failure = None;
+        for (i, prover_handle) in prover_handles.into_iter().enumerate() {
+            let res = prover_handle
+                .await
+                .map_err(|e| Chip0Error::ProverTaskFailed(e.to_string()))
+                .and_then(|res| res);
+            match res {
+                Ok(proof) => segments.push(proof),
+                Err(err) => {
+                    tracing::error!("Failed to prove segment {i}: {err}");
+                    failure = Some(Chip8Error::ProverError(format!("segment {i}: {err}")));
+                    break;
+                }
+            }
+        }
+        if let Some(proof_path) = &self.proof_path {
+            if let Err(err) = (SessionProof { segments }).save(proof_path) {
+                tracing::error!("Failed to save proof: {err}");
+            }
+        }
+        if let Some(err) = failure {
+            match status.checked_write() {
+                Ok(mut status) => *status = Err(err),
+                Err(lock_err) => tracing::error!("Failed to report {err}: {lock_err}"),
+            }
+        }
     }
 }
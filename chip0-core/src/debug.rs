@@ -0,0 +1,424 @@
+use chip8_core::{
+    cpu::{Cpu, SimpleCpu},
+    rng::Lfsr,
+};
+use core::fmt;
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
+use p3_field::{Field, PrimeField32};
+use p3_interaction::InteractionAir;
+use p3_keccak_air::NUM_KECCAK_COLS;
+use p3_matrix::{dense::RowMajorMatrix, Matrix};
+use std::collections::BTreeMap;
+
+use crate::{
+    bus::Chip0MachineBus,
+    chips::{
+        clear::columns::ClearCols, cpu::columns::CpuCols, draw::columns::DrawCols,
+        frame_buffer::columns::FrameBufferCols, hash::columns::HashCols,
+        keypad::columns::KeypadCols, memory::columns::MemoryCols, with_chip, Chip0MachineChip,
+    },
+};
+
+/// Evaluates the constraints of an AIR on a window of two concrete rows, recording the value of
+/// every asserted constraint in order.
+pub struct DebugConstraintBuilder<'a, F: Field> {
+    main: RowMajorMatrix<F>,
+    public_values: &'a [F],
+    is_first_row: F,
+    is_last_row: F,
+    is_transition: F,
+    constraints: Vec<F>,
+}
+
+impl<'a, F: Field> AirBuilder for DebugConstraintBuilder<'a, F> {
+    type F = F;
+    type Expr = F;
+    type Var = F;
+    type M = RowMajorMatrix<F>;
+
+    fn main(&self) -> Self::M {
+        self.main.clone()
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        self.is_first_row
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        self.is_last_row
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        if size == 2 {
+            self.is_transition
+        } else {
+            panic!("only supports a window size of 2")
+        }
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
+        self.constraints.push(x.into());
+    }
+}
+
+impl<'a, F: Field> AirBuilderWithPublicValues for DebugConstraintBuilder<'a, F> {
+    type PublicVar = F;
+
+    fn public_values(&self) -> &[Self::F] {
+        self.public_values
+    }
+}
+
+/// Constraint that doesn't hold on a row of a chip's trace.
+#[derive(Clone, Debug)]
+pub struct ConstraintFailure {
+    pub chip: &'static str,
+    pub row: usize,
+    /// Cycle of the row, for chips with a clk column
+    pub clk: Option<u64>,
+    /// Instruction executed in that cycle
+    pub instruction: Option<String>,
+    /// Index of the constraint in the order `Air::eval` asserts them
+    pub constraint: usize,
+    /// Columns of the row, or of the next one for `next.*`, that change the constraint's value
+    pub columns: Vec<String>,
+}
+
+impl fmt::Display for ConstraintFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} chip: constraint {} fails on row {}",
+            self.chip, self.constraint, self.row
+        )?;
+        if let Some(clk) = self.clk {
+            write!(f, " at clk {clk}")?;
+        }
+        if let Some(instruction) = &self.instruction {
+            write!(f, " ({instruction})")?;
+        }
+        write!(f, ", involving {}", self.columns.join(", "))
+    }
+}
+
+/// Bus whose sends and receives don't cancel out.
+#[derive(Clone, Debug)]
+pub struct UnbalancedBus {
+    pub bus: &'static str,
+    /// Tuples with a non-zero net count
+    pub unmatched: Vec<UnmatchedTuple>,
+}
+
+impl fmt::Display for UnbalancedBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bus: {} unmatched tuples",
+            self.bus,
+            self.unmatched.len()
+        )?;
+        for tuple in self.unmatched.iter() {
+            write!(f, "\n  {tuple}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Tuple sent more times than received on a bus, or the other way around.
+#[derive(Clone, Debug)]
+pub struct UnmatchedTuple {
+    pub fields: Vec<u32>,
+    /// Sends minus receives
+    pub count: i64,
+    /// Rows that send or receive the tuple
+    pub origins: Vec<TupleOrigin>,
+}
+
+impl fmt::Display for UnmatchedTuple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} with net count {}, from", self.fields, self.count)?;
+        for (i, origin) in self.origins.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{sep}{origin}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Row of a chip's trace that sends, with a positive count, or receives a tuple.
+#[derive(Clone, Debug)]
+pub struct TupleOrigin {
+    pub chip: &'static str,
+    pub row: usize,
+    pub count: i64,
+}
+
+impl fmt::Display for TupleOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.count > 0 { "sent" } else { "received" };
+        write!(
+            f,
+            "{} row {} ({kind} {}x)",
+            self.chip,
+            self.row,
+            self.count.abs()
+        )
+    }
+}
+
+/// Checks every constraint of `chips` on the matching trace matrices, as returned by
+/// `PartialMachineTrace::get_trace_matrices`. Interactions are checked by [`check_buses`].
+pub fn check_constraints<F: PrimeField32>(
+    chips: &[Chip0MachineChip],
+    traces: &[Option<RowMajorMatrix<F>>],
+    public_values: &[F],
+) -> Vec<ConstraintFailure> {
+    let instructions = instructions(chips, traces);
+
+    let mut failures = Vec::new();
+    for (chip, trace) in chips.iter().zip(traces.iter()) {
+        let Some(trace) = trace else {
+            continue;
+        };
+        #[cfg(feature = "trace-writer")]
+        let headers = with_chip!(chip, c => p3_air_util::TraceWriter::<F, F>::main_headers(c));
+        // Column names need the `trace-writer` feature, fall back to indices without it
+        #[cfg(not(feature = "trace-writer"))]
+        let headers: Vec<String> = (0..trace.width()).map(|i| format!("col_{i}")).collect();
+        let clk_col = clk_col::<F>(chip);
+
+        for row in 0..trace.height() {
+            let window = window(trace, row);
+            let constraints = eval(chip, &window, row, trace.height(), public_values);
+            let clk = clk_col.map(|col| trace.row_slice(row)[col].as_canonical_u64());
+            for (constraint, &value) in constraints.iter().enumerate() {
+                if value.is_zero() {
+                    continue;
+                }
+                let columns = involved_columns(
+                    chip,
+                    &window,
+                    row,
+                    trace.height(),
+                    public_values,
+                    constraint,
+                    value,
+                )
+                .into_iter()
+                .map(|col| {
+                    let width = trace.width();
+                    if col < width {
+                        headers[col].clone()
+                    } else {
+                        format!("next.{}", headers[col - width])
+                    }
+                })
+                .collect();
+                failures.push(ConstraintFailure {
+                    chip: chip.name(),
+                    row,
+                    clk,
+                    instruction: clk.and_then(|clk| instructions.get(&clk).cloned()),
+                    constraint,
+                    columns,
+                });
+            }
+        }
+    }
+    failures
+}
+
+/// Sums the counts of every tuple sent and received on each bus by `chips` over their traces and
+/// returns the buses where they don't cancel out, with the rows each unmatched tuple comes from.
+pub fn check_buses<F: PrimeField32>(
+    chips: &[Chip0MachineChip],
+    traces: &[Option<RowMajorMatrix<F>>],
+) -> Vec<UnbalancedBus> {
+    let mut tuples: BTreeMap<(usize, Vec<u32>), (F, Vec<TupleOrigin>)> = BTreeMap::new();
+    for (chip, trace) in chips.iter().zip(traces.iter()) {
+        let Some(trace) = trace else {
+            continue;
+        };
+        let preprocessed = with_chip!(chip, c => BaseAir::<F>::preprocessed_trace(c));
+        let sends = with_chip!(chip, c => InteractionAir::<F>::sends(c));
+        let receives = with_chip!(chip, c => InteractionAir::<F>::receives(c));
+        let interactions = sends
+            .iter()
+            .map(|interaction| (interaction, F::one()))
+            .chain(
+                receives
+                    .iter()
+                    .map(|interaction| (interaction, F::neg_one())),
+            );
+        for (interaction, sign) in interactions {
+            for row in 0..trace.height() {
+                let main = trace.row_slice(row);
+                let preprocessed = preprocessed
+                    .as_ref()
+                    .map(|preprocessed| preprocessed.row_slice(row).to_vec())
+                    .unwrap_or_default();
+                let count = sign * interaction.count.apply::<F, F>(&preprocessed, &main);
+                if count.is_zero() {
+                    continue;
+                }
+                let fields = interaction
+                    .fields
+                    .iter()
+                    .map(|field| field.apply::<F, F>(&preprocessed, &main).as_canonical_u32())
+                    .collect();
+                let (total, origins) = tuples
+                    .entry((interaction.argument_index, fields))
+                    .or_insert((F::zero(), Vec::new()));
+                *total += count;
+                origins.push(TupleOrigin {
+                    chip: chip.name(),
+                    row,
+                    count: signed(count),
+                });
+            }
+        }
+    }
+
+    let mut unbalanced: BTreeMap<usize, Vec<UnmatchedTuple>> = BTreeMap::new();
+    for ((bus, fields), (total, origins)) in tuples {
+        if !total.is_zero() {
+            unbalanced.entry(bus).or_default().push(UnmatchedTuple {
+                fields,
+                count: signed(total),
+                origins,
+            });
+        }
+    }
+    unbalanced
+        .into_iter()
+        .map(|(bus, unmatched)| UnbalancedBus {
+            bus: bus_name(bus),
+            unmatched,
+        })
+        .collect()
+}
+
+/// Field element as a signed integer, negative in the upper half of the field.
+fn signed<F: PrimeField32>(value: F) -> i64 {
+    let value = value.as_canonical_u32() as i64;
+    if value > (F::ORDER_U32 / 2) as i64 {
+        value - F::ORDER_U32 as i64
+    } else {
+        value
+    }
+}
+
+fn bus_name(bus: usize) -> &'static str {
+    match bus {
+        b if b == Chip0MachineBus::ClearBus as usize => "clear",
+        b if b == Chip0MachineBus::DrawBus as usize => "draw",
+        b if b == Chip0MachineBus::KeypadBus as usize => "keypad",
+        b if b == Chip0MachineBus::MemoryBus as usize => "memory",
+        b if b == Chip0MachineBus::FrameBufferBus as usize => "frame_buffer",
+        b if b == Chip0MachineBus::RangeBus as usize => "range",
+        b if b == Chip0MachineBus::MemoryStartBus as usize => "memory_start",
+        b if b == Chip0MachineBus::HashBus as usize => "hash",
+        b if b == Chip0MachineBus::RandomBus as usize => "random",
+        b if b == Chip0MachineBus::MemoryFinalBus as usize => "memory_final",
+        _ => "unknown",
+    }
+}
+
+/// Column holding the cycle of each row, for the chips that have one.
+fn clk_col<F: Field>(chip: &Chip0MachineChip) -> Option<usize> {
+    match chip {
+        Chip0MachineChip::Cpu(_) => Some(CpuCols::<F>::col_map().clk),
+        Chip0MachineChip::Clear(_) => Some(ClearCols::<F>::col_map().clk),
+        Chip0MachineChip::Draw(_) => Some(DrawCols::<F>::col_map().clk),
+        Chip0MachineChip::Keypad(_) => Some(KeypadCols::<F>::col_map().clk),
+        Chip0MachineChip::Memory(_) => Some(MemoryCols::<F>::col_map().clk),
+        Chip0MachineChip::FrameBuffer(_) => Some(FrameBufferCols::<F>::col_map().clk),
+        Chip0MachineChip::Hash(_) => Some(NUM_KECCAK_COLS + HashCols::<F>::col_map().clk),
+        Chip0MachineChip::Range(_)
+        | Chip0MachineChip::MemoryStart(_)
+        | Chip0MachineChip::MemoryFinal(_)
+        | Chip0MachineChip::Random(_) => None,
+    }
+}
+
+/// Disassembled instruction executed in each cycle, from the real rows of the CPU trace.
+fn instructions<F: PrimeField32>(
+    chips: &[Chip0MachineChip],
+    traces: &[Option<RowMajorMatrix<F>>],
+) -> BTreeMap<u64, String> {
+    let Some(Some(trace)) = chips
+        .iter()
+        .position(|chip| matches!(chip, Chip0MachineChip::Cpu(_)))
+        .map(|i| &traces[i])
+    else {
+        return BTreeMap::new();
+    };
+
+    let col_map = CpuCols::<F>::col_map();
+    let mut cpu = SimpleCpu::new(0, Lfsr::new(1));
+    trace
+        .rows()
+        .map(|row| row.collect::<Vec<_>>())
+        .filter(|row| row[col_map.is_real].is_one())
+        .map(|row| {
+            let opcode = row[col_map.opcode].as_canonical_u32() as u16;
+            let instruction = match cpu.decode(opcode) {
+                Ok(instruction) => format!("{opcode:04X} {instruction:?}"),
+                Err(_) => format!("{opcode:04X}"),
+            };
+            (row[col_map.clk].as_canonical_u64(), instruction)
+        })
+        .collect()
+}
+
+/// The row followed by the next one, wrapping around on the last row.
+fn window<F: Field>(trace: &RowMajorMatrix<F>, row: usize) -> RowMajorMatrix<F> {
+    let next = (row + 1) % trace.height();
+    let values = trace
+        .row_slice(row)
+        .iter()
+        .chain(trace.row_slice(next).iter())
+        .copied()
+        .collect();
+    RowMajorMatrix::new(values, trace.width())
+}
+
+fn eval<F: PrimeField32>(
+    chip: &Chip0MachineChip,
+    window: &RowMajorMatrix<F>,
+    row: usize,
+    height: usize,
+    public_values: &[F],
+) -> Vec<F> {
+    let mut builder = DebugConstraintBuilder {
+        main: window.clone(),
+        public_values,
+        is_first_row: F::from_bool(row == 0),
+        is_last_row: F::from_bool(row == height - 1),
+        is_transition: F::from_bool(row != height - 1),
+        constraints: Vec::new(),
+    };
+    with_chip!(chip, c => c.eval(&mut builder));
+    builder.constraints
+}
+
+/// Indices into the window of the cells whose value the constraint depends on, found by
+/// perturbing one cell at a time. The AIRs assert the same constraints in the same order on
+/// every row, so the index identifies the constraint across evaluations.
+fn involved_columns<F: PrimeField32>(
+    chip: &Chip0MachineChip,
+    window: &RowMajorMatrix<F>,
+    row: usize,
+    height: usize,
+    public_values: &[F],
+    constraint: usize,
+    value: F,
+) -> Vec<usize> {
+    (0..window.values.len())
+        .filter(|&i| {
+            let mut perturbed = window.clone();
+            perturbed.values[i] += F::one();
+            eval(chip, &perturbed, row, height, public_values)[constraint] != value
+        })
+        .collect()
+}
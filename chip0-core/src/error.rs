@@ -0,0 +1,46 @@
+use thiserror::Error;
+
+use super::debug::{ConstraintFailure, UnbalancedBus};
+
+#[derive(Error, Debug, Clone)]
+pub enum Chip0Error {
+    #[error("IO error: {0}")]
+    IoError(String),
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+    #[error("Invalid ROM: {0}")]
+    InvalidRom(String),
+    #[error("ROM hash mismatch")]
+    RomHashMismatch,
+    #[error("Proof was generated with different quirks")]
+    QuirksMismatch,
+    #[error("Memory digest mismatch")]
+    MemoryDigestMismatch,
+    #[error("Invalid public values")]
+    InvalidPublicValues,
+    #[error("Verifying key mismatch")]
+    VerifyingKeyMismatch,
+    #[error("Verification error: {0}")]
+    VerificationError(String),
+    #[error(
+        "{} constraint failures and {} unbalanced buses",
+        .failures.len(),
+        .unbalanced.len()
+    )]
+    ConstraintFailures {
+        failures: Vec<ConstraintFailure>,
+        unbalanced: Vec<UnbalancedBus>,
+    },
+    #[error("Prover task failed: {0}")]
+    ProverTaskFailed(String),
+    #[error("Session has no segments")]
+    EmptySession,
+    #[error("First segment doesn't start from the initial state")]
+    InitialStateMismatch,
+    #[error("Segment {0} doesn't start from the end state of the previous segment")]
+    SegmentChainMismatch(usize),
+    #[error("Segment doesn't start on a timer tick")]
+    MisalignedSegment,
+    #[error("Session was played with a different random seed")]
+    RandomSeedMismatch,
+}
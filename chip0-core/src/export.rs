@@ -0,0 +1,82 @@
+use p3_air::BaseAir;
+use p3_air_util::TraceWriter;
+use p3_field::PrimeField32;
+use p3_matrix::{dense::RowMajorMatrix, Matrix};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::chips::{with_chip, Chip0MachineChip};
+use super::error::Chip0Error;
+
+/// Name of the file listing the exported matrices.
+pub const MANIFEST_FILE: &str = "manifest.csv";
+
+/// Writes the trace matrices of `chips`, as returned by `PartialMachineTrace::get_trace_matrices`,
+/// to one CSV file per chip in `dir`, with the column names as header. Chips with a preprocessed
+/// trace also get a `<chip>_preprocessed.csv` file. The manifest lists the chip, kind, file and
+/// dimensions of every matrix written.
+pub fn write_traces<F: PrimeField32>(
+    dir: impl AsRef<Path>,
+    chips: &[Chip0MachineChip],
+    traces: &[Option<RowMajorMatrix<F>>],
+) -> Result<(), Chip0Error> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(|e| Chip0Error::IoError(e.to_string()))?;
+
+    let mut manifest = vec!["chip,kind,file,rows,columns".to_string()];
+    for (chip, trace) in chips.iter().zip(traces.iter()) {
+        let Some(trace) = trace else {
+            continue;
+        };
+        let file = format!("{}.csv", chip.name());
+        let headers = with_chip!(chip, c => TraceWriter::<F, F>::main_headers(c));
+        write_matrix(dir.join(&file), &headers, trace)?;
+        manifest.push(format!(
+            "{},main,{file},{},{}",
+            chip.name(),
+            trace.height(),
+            trace.width()
+        ));
+
+        if let Some(preprocessed) = with_chip!(chip, c => BaseAir::<F>::preprocessed_trace(c)) {
+            let file = format!("{}_preprocessed.csv", chip.name());
+            let headers = with_chip!(chip, c => TraceWriter::<F, F>::preprocessed_headers(c));
+            write_matrix(dir.join(&file), &headers, &preprocessed)?;
+            manifest.push(format!(
+                "{},preprocessed,{file},{},{}",
+                chip.name(),
+                preprocessed.height(),
+                preprocessed.width()
+            ));
+        }
+    }
+
+    manifest.push(String::new());
+    fs::write(dir.join(MANIFEST_FILE), manifest.join("\n"))
+        .map_err(|e| Chip0Error::IoError(e.to_string()))
+}
+
+fn write_matrix<F: PrimeField32>(
+    path: impl AsRef<Path>,
+    headers: &[String],
+    matrix: &RowMajorMatrix<F>,
+) -> Result<(), Chip0Error> {
+    let file = File::create(path).map_err(|e| Chip0Error::IoError(e.to_string()))?;
+    let mut writer = BufWriter::new(file);
+    let mut write_line =
+        |line: String| writeln!(writer, "{line}").map_err(|e| Chip0Error::IoError(e.to_string()));
+    write_line(headers.join(","))?;
+    for row in matrix.rows() {
+        let line = row
+            .map(|value| value.as_canonical_u32().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write_line(line)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| Chip0Error::IoError(e.to_string()))
+}
@@ -3,6 +3,12 @@ pub mod bus;
 pub mod chips;
 pub mod config;
 pub mod cpu;
+pub mod debug;
+pub mod error;
+#[cfg(feature = "trace-writer")]
+pub mod export;
 pub mod machine;
+pub mod proof;
 pub mod prover;
+pub mod public_values;
 pub mod trace;
@@ -1,3 +1,7 @@
+use chip8_core::{
+    quirks::Quirks,
+    state::{SimpleState, State},
+};
 use p3_field::PrimeField32;
 use p3_machine::machine::Machine;
 use p3_uni_stark::{StarkGenericConfig, Val};
@@ -6,19 +10,32 @@ use crate::{
     bus::Chip0MachineBus,
     chips::{
         clear::ClearChip, cpu::CpuChip, draw::DrawChip, frame_buffer::FrameBufferChip,
-        keypad::KeypadChip, memory::MemoryChip, memory_start::MemoryStartChip, range::RangeChip,
-        Chip0MachineChip,
+        hash::HashChip, keypad::KeypadChip, memory::MemoryChip, memory_final::MemoryFinalChip,
+        memory_start::MemoryStartChip, random::RandomChip, range::RangeChip, Chip0MachineChip,
     },
+    error::Chip0Error,
 };
 
+/// Memory image with the font and the ROM loaded, before the first cycle.
+pub fn initial_memory(rom: &[u8]) -> Result<Vec<u8>, Chip0Error> {
+    let mut state = SimpleState::default();
+    state
+        .load_rom(rom)
+        .map_err(|e| Chip0Error::InvalidRom(e.to_string()))?;
+    Ok(state.memory.to_vec())
+}
+
+/// Machine proving a single segment that starts from the `memory` image.
 #[derive(Default, Clone)]
 pub struct Chip0Machine {
-    pub rom: Vec<u8>,
+    pub memory: Vec<u8>,
+    /// Quirks the CPU and draw constraints are specialized for.
+    pub quirks: Quirks,
 }
 
 impl Chip0Machine {
-    pub fn new(rom: Vec<u8>) -> Self {
-        Self { rom }
+    pub fn new(memory: Vec<u8>, quirks: Quirks) -> Self {
+        Self { memory, quirks }
     }
 }
 
@@ -36,6 +53,9 @@ where
             Chip0MachineBus::DrawBus as usize,
             Chip0MachineBus::MemoryBus as usize,
             Chip0MachineBus::KeypadBus as usize,
+            Chip0MachineBus::RangeBus as usize,
+            Chip0MachineBus::RandomBus as usize,
+            self.quirks,
         );
         let clear_chip = ClearChip::new(
             Chip0MachineBus::ClearBus as usize,
@@ -45,10 +65,16 @@ where
             Chip0MachineBus::DrawBus as usize,
             Chip0MachineBus::FrameBufferBus as usize,
             Chip0MachineBus::MemoryBus as usize,
+            Chip0MachineBus::RangeBus as usize,
+            self.quirks.clip,
+        );
+        let keypad_chip = KeypadChip::new(
+            Chip0MachineBus::KeypadBus as usize,
+            Chip0MachineBus::HashBus as usize,
         );
-        let keypad_chip = KeypadChip::new(Chip0MachineBus::KeypadBus as usize);
         let memory_chip = MemoryChip::new(
             Chip0MachineBus::MemoryStartBus as usize,
+            Chip0MachineBus::MemoryFinalBus as usize,
             Chip0MachineBus::MemoryBus as usize,
             Chip0MachineBus::RangeBus as usize,
         );
@@ -57,8 +83,14 @@ where
             Chip0MachineBus::RangeBus as usize,
         );
         let range_chip = RangeChip::new(Chip0MachineBus::RangeBus as usize);
-        let memory_start_chip =
-            MemoryStartChip::new(self.rom.clone(), Chip0MachineBus::MemoryStartBus as usize);
+        let memory_start_chip = MemoryStartChip::new(
+            self.memory.clone(),
+            Chip0MachineBus::MemoryStartBus as usize,
+            Chip0MachineBus::MemoryFinalBus as usize,
+        );
+        let memory_final_chip = MemoryFinalChip::new(Chip0MachineBus::MemoryFinalBus as usize);
+        let hash_chip = HashChip::new(Chip0MachineBus::HashBus as usize);
+        let random_chip = RandomChip::new(Chip0MachineBus::RandomBus as usize);
 
         vec![
             Chip0MachineChip::Cpu(cpu_chip),
@@ -69,6 +101,9 @@ where
             Chip0MachineChip::FrameBuffer(frame_buffer_chip),
             Chip0MachineChip::Range(range_chip),
             Chip0MachineChip::MemoryStart(memory_start_chip),
+            Chip0MachineChip::MemoryFinal(memory_final_chip),
+            Chip0MachineChip::Hash(hash_chip),
+            Chip0MachineChip::Random(random_chip),
         ]
     }
 }
@@ -0,0 +1,131 @@
+use chip8_core::{constants::NUM_REGISTERS, quirks::Quirks};
+use core::borrow::Borrow;
+use p3_field::PrimeField32;
+use p3_machine::proof::{MachineProof, VerifyingKey};
+use p3_uni_stark::{StarkGenericConfig, Val};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::error::Chip0Error;
+use crate::public_values::{PublicValues, DIGEST_SIZE};
+
+pub const ROM_HASH_SIZE: usize = 32;
+
+pub fn rom_hash(rom: &[u8]) -> [u8; ROM_HASH_SIZE] {
+    let mut hasher = Keccak::v256();
+    hasher.update(rom);
+
+    let mut hash = [0; ROM_HASH_SIZE];
+    hasher.finalize(&mut hash);
+    hash
+}
+
+fn save(value: &impl Serialize, path: impl AsRef<Path>) -> Result<(), Chip0Error> {
+    let file = File::create(path).map_err(|e| Chip0Error::IoError(e.to_string()))?;
+    bincode::serialize_into(BufWriter::new(file), value)
+        .map_err(|e| Chip0Error::SerializationError(e.to_string()))
+}
+
+fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, Chip0Error> {
+    let file = File::open(path).map_err(|e| Chip0Error::IoError(e.to_string()))?;
+    bincode::deserialize_from(BufReader::new(file))
+        .map_err(|e| Chip0Error::SerializationError(e.to_string()))
+}
+
+/// Self-contained proof of a segment that can be checked without re-running the emulator.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Chip0Proof<SC: StarkGenericConfig> {
+    pub rom: Vec<u8>,
+    pub rom_hash: [u8; ROM_HASH_SIZE],
+    /// Memory image at the start of the segment
+    pub memory: Vec<u8>,
+    /// Quirks the segment was executed with
+    pub quirks: Quirks,
+    pub public_values: Vec<Val<SC>>,
+    pub verifying_key: VerifyingKey<SC>,
+    pub proof: MachineProof<SC>,
+}
+
+impl<SC: StarkGenericConfig> Chip0Proof<SC> {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Chip0Error> {
+        save(self, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Chip0Error> {
+        load(path)
+    }
+}
+
+/// Outcome of a play session as committed in the public values of its segments.
+#[derive(Clone, Debug)]
+pub struct SessionOutcome {
+    pub rom_hash: [u8; ROM_HASH_SIZE],
+    /// Seed of the CXNN generator, the random state at the start of the first segment
+    pub random_seed: u16,
+    /// Running hash of the input log of the whole session
+    pub input_digest: [u16; DIGEST_SIZE],
+    pub end_clk: u64,
+    pub end_program_counter: u16,
+    pub end_registers: [u8; NUM_REGISTERS],
+    pub end_index_register: u16,
+    /// Pixels of the final frame buffer, row by row
+    pub end_frame_buffer: Vec<u8>,
+}
+
+/// Proofs of consecutive segments of a play session.
+///
+/// Verifiers check the segments one by one and that they chain from the initial state.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SessionProof<SC: StarkGenericConfig> {
+    pub segments: Vec<Chip0Proof<SC>>,
+}
+
+impl<SC: StarkGenericConfig> SessionProof<SC>
+where
+    Val<SC>: PrimeField32,
+{
+    /// What the session proves, from the public values of its first and last segments.
+    pub fn outcome(&self) -> Result<SessionOutcome, Chip0Error> {
+        let (first, last) = match (self.segments.first(), self.segments.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Chip0Error::EmptySession),
+        };
+        for segment in [first, last] {
+            if segment.public_values.len() != PublicValues::<Val<SC>>::num_cols() {
+                return Err(Chip0Error::InvalidPublicValues);
+            }
+        }
+        let start: &PublicValues<Val<SC>> = first.public_values.as_slice().borrow();
+        let end: &PublicValues<Val<SC>> = last.public_values.as_slice().borrow();
+
+        Ok(SessionOutcome {
+            rom_hash: first.rom_hash,
+            random_seed: start.start_random_state.as_canonical_u32() as u16,
+            input_digest: end.input_digest().map(|v| v.as_canonical_u32() as u16),
+            end_clk: end.end_clk.as_canonical_u64(),
+            end_program_counter: end.end_program_counter.as_canonical_u32() as u16,
+            end_registers: end.end_registers.map(|v| v.as_canonical_u32() as u8),
+            end_index_register: end.end_index_register.as_canonical_u32() as u16,
+            end_frame_buffer: end
+                .end_frame_buffer
+                .iter()
+                .map(|v| v.as_canonical_u32() as u8)
+                .collect(),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Chip0Error> {
+        save(self, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Chip0Error> {
+        load(path)
+    }
+}
@@ -1,9 +1,23 @@
-use p3_field::PrimeField32;
+use chip8_core::{
+    constants::{PROGRAM_START_ADDRESS, TICKS_PER_TIMER},
+    quirks::Quirks,
+};
+use core::borrow::Borrow;
+use itertools::Itertools;
+use p3_field::{AbstractField, PrimeField32};
 use p3_machine::machine::Machine;
 use p3_uni_stark::{StarkGenericConfig, Val};
+#[cfg(feature = "trace-writer")]
+use std::path::PathBuf;
 
 use super::config::{default_challenger, default_config, Challenger, MyConfig};
-use super::machine::Chip0Machine;
+use super::debug::{check_buses, check_constraints};
+use super::error::Chip0Error;
+#[cfg(feature = "trace-writer")]
+use super::export::write_traces;
+use super::machine::{initial_memory, Chip0Machine};
+use super::proof::{rom_hash, Chip0Proof, SessionProof};
+use super::public_values::{memory_digest, PublicValues};
 use super::trace::PartialMachineTrace;
 
 #[derive(Clone)]
@@ -12,17 +26,47 @@ where
     SC: StarkGenericConfig,
     Val<SC>: PrimeField32,
 {
-    machine: Chip0Machine,
+    rom: Vec<u8>,
+    quirks: Quirks,
+    debug: bool,
+    /// Directory the trace matrices of each segment are exported to
+    #[cfg(feature = "trace-writer")]
+    trace_dir: Option<PathBuf>,
     config: SC,
 }
 
 impl DefaultProver<MyConfig> {
     pub fn new(rom: Vec<u8>) -> Self {
         Self {
-            machine: Chip0Machine::new(rom),
+            rom,
+            quirks: Quirks::default(),
+            debug: false,
+            #[cfg(feature = "trace-writer")]
+            trace_dir: None,
             config: default_config(),
         }
     }
+
+    /// Proves and verifies execution with the given quirks.
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Checks every constraint and bus on the traces before proving, returning the violations as
+    /// `Chip0Error::ConstraintFailures` instead of failing with an opaque prover error.
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Exports the trace matrices of every segment to `<trace_dir>/segment_<start clk>` before
+    /// proving it.
+    #[cfg(feature = "trace-writer")]
+    pub fn with_trace_dir(mut self, trace_dir: Option<PathBuf>) -> Self {
+        self.trace_dir = trace_dir;
+        self
+    }
 }
 
 pub trait Prover<SC>
@@ -30,7 +74,12 @@ where
     SC: StarkGenericConfig,
     Val<SC>: PrimeField32,
 {
-    fn prove(&self, partial_trace: PartialMachineTrace<Val<SC>>);
+    fn prove(
+        &self,
+        partial_trace: PartialMachineTrace<Val<SC>>,
+    ) -> Result<Chip0Proof<SC>, Chip0Error>;
+
+    fn verify(&self, proof: &Chip0Proof<SC>) -> Result<(), Chip0Error>;
 
     fn new_challenger(&self) -> Challenger;
 }
@@ -40,21 +89,140 @@ impl Prover<MyConfig> for DefaultProver<MyConfig> {
         default_challenger()
     }
 
-    fn prove(&self, partial_trace: PartialMachineTrace<Val<MyConfig>>) {
-        let (pk, vk) = self.machine.setup(&self.config);
+    fn prove(
+        &self,
+        partial_trace: PartialMachineTrace<Val<MyConfig>>,
+    ) -> Result<Chip0Proof<MyConfig>, Chip0Error> {
+        let memory = partial_trace.start.memory.clone();
+        let machine = Chip0Machine::new(memory.clone(), self.quirks);
+        let (pk, vk) = machine.setup(&self.config);
 
+        let public_values = partial_trace.public_values();
         let traces = partial_trace.get_trace_matrices();
-        let public_values = vec![];
+
+        #[cfg(feature = "trace-writer")]
+        if let Some(trace_dir) = &self.trace_dir {
+            let chips = Machine::<'_, MyConfig>::chips(&machine);
+            let public_values: &PublicValues<Val<MyConfig>> = public_values.as_slice().borrow();
+            let dir = trace_dir.join(format!(
+                "segment_{}",
+                public_values.start_clk.as_canonical_u32()
+            ));
+            if let Err(err) = write_traces(&dir, &chips, &traces) {
+                tracing::error!("Failed to export trace to {}: {err}", dir.display());
+            }
+        }
+
+        if self.debug {
+            let chips = Machine::<'_, MyConfig>::chips(&machine);
+            let failures = check_constraints(&chips, &traces, &public_values);
+            for failure in failures.iter() {
+                tracing::error!("{failure}");
+            }
+            let unbalanced = check_buses(&chips, &traces);
+            for bus in unbalanced.iter() {
+                tracing::error!("{bus}");
+            }
+            if !failures.is_empty() || !unbalanced.is_empty() {
+                return Err(Chip0Error::ConstraintFailures {
+                    failures,
+                    unbalanced,
+                });
+            }
+        }
 
         let mut challenger = self.new_challenger();
-        let proof = self
-            .machine
-            .prove(&self.config, &mut challenger, &pk, traces, &public_values);
+        let proof = machine.prove(&self.config, &mut challenger, &pk, traces, &public_values);
+
+        Ok(Chip0Proof {
+            rom: self.rom.clone(),
+            rom_hash: rom_hash(&self.rom),
+            memory,
+            quirks: self.quirks,
+            public_values,
+            verifying_key: vk,
+            proof,
+        })
+    }
+
+    fn verify(&self, proof: &Chip0Proof<MyConfig>) -> Result<(), Chip0Error> {
+        if proof.rom != self.rom || proof.rom_hash != rom_hash(&proof.rom) {
+            return Err(Chip0Error::RomHashMismatch);
+        }
+        if proof.quirks != self.quirks {
+            return Err(Chip0Error::QuirksMismatch);
+        }
+        if proof.public_values.len() != PublicValues::<Val<MyConfig>>::num_cols() {
+            return Err(Chip0Error::InvalidPublicValues);
+        }
+        let public_values: &PublicValues<Val<MyConfig>> = proof.public_values.as_slice().borrow();
+        if public_values.start_clk.as_canonical_u32() as u64 % TICKS_PER_TIMER != 0 {
+            return Err(Chip0Error::MisalignedSegment);
+        }
+        let expected_memory_digest =
+            memory_digest(&proof.memory).map(Val::<MyConfig>::from_canonical_u16);
+        if public_values.start_memory_digest != expected_memory_digest {
+            return Err(Chip0Error::MemoryDigestMismatch);
+        }
+
+        // The verifying key is derived from the start memory, so never trust the serialized one
+        let machine = Chip0Machine::new(proof.memory.clone(), self.quirks);
+        let (_, vk) = machine.setup(&self.config);
+        let expected_vk =
+            bincode::serialize(&vk).map_err(|e| Chip0Error::SerializationError(e.to_string()))?;
+        let actual_vk = bincode::serialize(&proof.verifying_key)
+            .map_err(|e| Chip0Error::SerializationError(e.to_string()))?;
+        if expected_vk != actual_vk {
+            return Err(Chip0Error::VerifyingKeyMismatch);
+        }
 
-        // TODO: Avoid clone
         let mut challenger = self.new_challenger();
-        self.machine
-            .verify(&self.config, &mut challenger, &vk, &proof, &public_values)
-            .unwrap();
+        machine
+            .verify(
+                &self.config,
+                &mut challenger,
+                &vk,
+                &proof.proof,
+                &proof.public_values,
+            )
+            .map_err(|e| Chip0Error::VerificationError(format!("{e:?}")))
     }
 }
+
+/// Verifies every segment of a play session and that they chain from the initial state.
+pub fn verify(proof: &SessionProof<MyConfig>) -> Result<(), Chip0Error> {
+    let first = proof.segments.first().ok_or(Chip0Error::EmptySession)?;
+    let prover = DefaultProver::new(first.rom.clone()).with_quirks(first.quirks);
+    for segment in proof.segments.iter() {
+        prover.verify(segment)?;
+    }
+
+    let public_values: &PublicValues<Val<MyConfig>> = first.public_values.as_slice().borrow();
+    let zero = Val::<MyConfig>::zero();
+    let is_initial = public_values.start_clk == zero
+        && public_values.start_program_counter
+            == Val::<MyConfig>::from_canonical_u16(PROGRAM_START_ADDRESS)
+        && public_values.start_registers.iter().all(|&v| v == zero)
+        && public_values.start_index_register == zero
+        && public_values.start_stack.iter().all(|&v| v == zero)
+        && public_values.start_stack_pointer == zero
+        && public_values.start_delay_timer == zero
+        && public_values.start_sound_timer == zero
+        && public_values.start_keypad.iter().all(|&v| v == zero)
+        && public_values.start_wait_key == zero
+        && public_values.start_input_state.iter().all(|&v| v == zero)
+        && public_values.start_frame_buffer.iter().all(|&v| v == zero);
+    if !is_initial || first.memory != initial_memory(&first.rom)? {
+        return Err(Chip0Error::InitialStateMismatch);
+    }
+
+    for (i, (prev, next)) in proof.segments.iter().tuple_windows().enumerate() {
+        let prev: &PublicValues<Val<MyConfig>> = prev.public_values.as_slice().borrow();
+        let next: &PublicValues<Val<MyConfig>> = next.public_values.as_slice().borrow();
+        if !prev.chains_to(next) {
+            return Err(Chip0Error::SegmentChainMismatch(i + 1));
+        }
+    }
+
+    Ok(())
+}
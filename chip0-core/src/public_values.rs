@@ -0,0 +1,188 @@
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, NUM_KEYS, NUM_REGISTERS, STACK_DEPTH};
+use p3_derive::Columnar;
+use tiny_keccak::keccakf;
+
+/// Number of 16-bit limbs in a 256-bit digest
+pub const DIGEST_SIZE: usize = 16;
+pub const FRAME_BUFFER_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+
+pub const KECCAK_STATE_SIZE: usize = 25;
+/// Number of 16-bit limbs in a Keccak-f[1600] state
+pub const SPONGE_STATE_SIZE: usize = 4 * KECCAK_STATE_SIZE;
+/// Lanes of the sponge state overwritten by each absorbed input event
+pub const INPUT_EVENT_LANES: usize = 3;
+/// Bytes in a lane of the sponge state
+pub const LANE_BYTES: usize = 8;
+/// Lanes of the sponge state overwritten by each absorbed chunk of memory
+pub const MEMORY_CHUNK_LANES: usize = 16;
+pub const MEMORY_CHUNK_SIZE: usize = MEMORY_CHUNK_LANES * LANE_BYTES;
+
+/// Public values of a segment proof.
+///
+/// The `start_*` values hold the machine state before the first cycle of the segment and the
+/// `end_*` values the state after its last cycle, so consecutive segments chain when the end
+/// state of one equals the start state of the next.
+///
+/// The ROM has no digest here: it is bound through the start memory image, which is the
+/// preprocessed trace of the memory start chip and so part of the verifying key. Verifiers check
+/// that the first segment starts from the font and ROM image, and the others chain from it.
+///
+/// The end memory digest is the hash of the final memory computed in-circuit by the memory final
+/// chip. The start memory digest is checked against the start image outside the circuit.
+///
+/// The input state is the sponge absorbing the input log, hashed in-circuit by the hash chip. It
+/// is carried across segments like the rest of the state, so the end input state of the last
+/// segment commits to the input log of the whole session.
+#[repr(C)]
+#[derive(Columnar)]
+pub struct PublicValues<T> {
+    pub start_clk: T,
+    pub start_program_counter: T,
+    pub start_registers: [T; NUM_REGISTERS],
+    pub start_index_register: T,
+    pub start_stack: [T; STACK_DEPTH],
+    pub start_stack_pointer: T,
+    pub start_delay_timer: T,
+    pub start_sound_timer: T,
+    pub start_keypad: [T; NUM_KEYS],
+    pub start_random_state: T,
+    pub start_wait_key: T,
+    pub start_input_state: [T; SPONGE_STATE_SIZE],
+    pub start_memory_digest: [T; DIGEST_SIZE],
+    pub start_frame_buffer: [T; FRAME_BUFFER_SIZE],
+
+    pub end_clk: T,
+    pub end_program_counter: T,
+    pub end_registers: [T; NUM_REGISTERS],
+    pub end_index_register: T,
+    pub end_stack: [T; STACK_DEPTH],
+    pub end_stack_pointer: T,
+    pub end_delay_timer: T,
+    pub end_sound_timer: T,
+    pub end_keypad: [T; NUM_KEYS],
+    pub end_random_state: T,
+    pub end_wait_key: T,
+    pub end_input_state: [T; SPONGE_STATE_SIZE],
+    pub end_memory_digest: [T; DIGEST_SIZE],
+    pub end_frame_buffer: [T; FRAME_BUFFER_SIZE],
+}
+
+impl<T: Copy + PartialEq> PublicValues<T> {
+    /// Machine state before the first cycle of the segment.
+    pub fn start_state(&self) -> Vec<T> {
+        [self.start_clk, self.start_program_counter]
+            .into_iter()
+            .chain(self.start_registers)
+            .chain([self.start_index_register])
+            .chain(self.start_stack)
+            .chain([
+                self.start_stack_pointer,
+                self.start_delay_timer,
+                self.start_sound_timer,
+            ])
+            .chain(self.start_keypad)
+            .chain([self.start_random_state, self.start_wait_key])
+            .chain(self.start_input_state)
+            .chain(self.start_memory_digest)
+            .chain(self.start_frame_buffer)
+            .collect()
+    }
+
+    /// Machine state after the last cycle of the segment, in the same order as `start_state`.
+    pub fn end_state(&self) -> Vec<T> {
+        [self.end_clk, self.end_program_counter]
+            .into_iter()
+            .chain(self.end_registers)
+            .chain([self.end_index_register])
+            .chain(self.end_stack)
+            .chain([
+                self.end_stack_pointer,
+                self.end_delay_timer,
+                self.end_sound_timer,
+            ])
+            .chain(self.end_keypad)
+            .chain([self.end_random_state, self.end_wait_key])
+            .chain(self.end_input_state)
+            .chain(self.end_memory_digest)
+            .chain(self.end_frame_buffer)
+            .collect()
+    }
+
+    /// Running hash of the input log up to the end of the segment, the first 256 bits of the end
+    /// input state.
+    ///
+    /// Only events that change the key state are hashed. Repeated presses of a held key and
+    /// releases of a key that isn't held are dropped before they reach the keypad, so two logs
+    /// that differ only by such events have the same digest.
+    pub fn input_digest(&self) -> [T; DIGEST_SIZE] {
+        core::array::from_fn(|i| self.end_input_state[i])
+    }
+
+    /// Whether `next` starts from the state this segment ends in.
+    pub fn chains_to(&self, next: &Self) -> bool {
+        self.end_state() == next.start_state()
+    }
+}
+
+/// Sponge state as 16-bit limbs, lane by lane and from the low limb of each lane.
+pub fn sponge_limbs(state: &[u64; KECCAK_STATE_SIZE]) -> [u16; SPONGE_STATE_SIZE] {
+    let mut limbs = [0; SPONGE_STATE_SIZE];
+    for (lane_limbs, lane) in limbs.chunks_exact_mut(4).zip(state.iter()) {
+        for (i, limb) in lane_limbs.iter_mut().enumerate() {
+            *limb = (lane >> (16 * i)) as u16;
+        }
+    }
+    limbs
+}
+
+/// First 256 bits of a sponge state, as 16-bit limbs.
+fn sponge_digest(state: &[u64; KECCAK_STATE_SIZE]) -> [u16; DIGEST_SIZE] {
+    let limbs = sponge_limbs(state);
+    core::array::from_fn(|i| limbs[i])
+}
+
+/// Keccak-f[1600] inputs of the hash over a memory image.
+///
+/// Each chunk of `MEMORY_CHUNK_SIZE` bytes overwrites the first lanes of the state, starting from
+/// the zero state, before it is permuted.
+pub fn memory_preimages(
+    memory: &[u8],
+) -> (Vec<[u64; KECCAK_STATE_SIZE]>, [u64; KECCAK_STATE_SIZE]) {
+    let mut state = [0u64; KECCAK_STATE_SIZE];
+    let mut preimages = vec![];
+    for chunk in memory.chunks_exact(MEMORY_CHUNK_SIZE) {
+        for (lane, bytes) in state.iter_mut().zip(chunk.chunks_exact(LANE_BYTES)) {
+            *lane = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        preimages.push(state);
+        keccakf(&mut state);
+    }
+    (preimages, state)
+}
+
+/// Digest of a full memory image, the first 256 bits of the final sponge state.
+///
+/// This is the hash computed in-circuit by the memory final chip.
+pub fn memory_digest(memory: &[u8]) -> [u16; DIGEST_SIZE] {
+    let (_, state) = memory_preimages(memory);
+    sponge_digest(&state)
+}
+
+/// Keccak-f[1600] inputs of the running hash over the `(clk, key, kind)` input log.
+///
+/// Starting from `state`, which is the zero state at the start of a session and the end state of
+/// the previous segment otherwise, each event overwrites the first lanes of the state before it
+/// is permuted.
+pub fn input_preimages(
+    mut state: [u64; KECCAK_STATE_SIZE],
+    events: impl IntoIterator<Item = (u64, u8, u8)>,
+) -> (Vec<[u64; KECCAK_STATE_SIZE]>, [u64; KECCAK_STATE_SIZE]) {
+    let mut preimages = vec![];
+    for (clk, key, kind) in events {
+        let lanes: [u64; INPUT_EVENT_LANES] = [clk, key as u64, kind as u64];
+        state[..INPUT_EVENT_LANES].copy_from_slice(&lanes);
+        preimages.push(state);
+        keccakf(&mut state);
+    }
+    (preimages, state)
+}
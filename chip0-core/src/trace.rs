@@ -1,26 +1,46 @@
 use chip8_core::{
+    audio::AudioPattern,
     constants::{
-        DISPLAY_HEIGHT, DISPLAY_WIDTH, FLAG_REGISTER, MEMORY_SIZE, NUM_KEYS, OPCODE_SIZE,
-        PROGRAM_START_ADDRESS, STACK_DEPTH,
+        AUDIO_PATTERN_SIZE, DISPLAY_HEIGHT, DISPLAY_WIDTH, FLAG_REGISTER, MEMORY_SIZE, NUM_KEYS,
+        NUM_REGISTERS, OPCODE_SIZE, PROGRAM_START_ADDRESS, TICKS_PER_TIMER,
     },
     error::Chip8Error,
+    frame_buffer::FrameBuffer,
     input::InputKind,
     keypad::Key,
+    quirks::Quirks,
+    rng::{LFSR_SHIFTS_PER_BYTE, LFSR_TAPS},
+    rwlock::CheckedRead,
     state::{Address, SimpleState, State, Word},
 };
-use core::slice;
+use core::{borrow::BorrowMut, mem, slice};
 use itertools::Itertools;
 use p3_field::PrimeField32;
-use p3_matrix::dense::RowMajorMatrix;
+use p3_keccak_air::{generate_trace_rows, NUM_KECCAK_COLS, NUM_ROUNDS};
+use p3_matrix::{dense::RowMajorMatrix, Matrix};
 use std::{
     collections::{BTreeMap, BTreeSet},
     sync::{Arc, RwLock},
 };
 
-use crate::chips::{
-    clear::columns::ClearCols, cpu::columns::CpuCols, draw::columns::DrawCols,
-    frame_buffer::columns::FrameBufferCols, keypad::columns::KeypadCols,
-    memory::columns::MemoryCols, memory_start::columns::MemoryStartCols, range::columns::RangeCols,
+use crate::{
+    chips::{
+        clear::columns::ClearCols,
+        cpu::columns::{CpuCols, BCD_DIGIT_OFFSET, NIBBLE_BITS},
+        draw::columns::{DrawCols, WORD_BITS},
+        frame_buffer::columns::FrameBufferCols,
+        hash::columns::HashCols,
+        keypad::columns::KeypadCols,
+        memory::columns::MemoryCols,
+        memory_final::columns::MemoryFinalCols,
+        memory_start::columns::MemoryStartCols,
+        random::columns::RandomCols,
+        range::columns::RangeCols,
+    },
+    public_values::{
+        input_preimages, memory_digest, memory_preimages, sponge_limbs, PublicValues,
+        FRAME_BUFFER_SIZE, KECCAK_STATE_SIZE, LANE_BYTES, MEMORY_CHUNK_LANES, MEMORY_CHUNK_SIZE,
+    },
 };
 
 #[derive(Default, Clone)]
@@ -39,8 +59,35 @@ pub struct MemoryEventLike<T> {
     pub is_read: T,
 }
 
+/// Machine state at the boundary between two segments.
+#[derive(Clone)]
+pub struct SegmentState<F: PrimeField32> {
+    /// Row holding the CPU state, in the same layout as the final CPU row
+    pub cpu: CpuCols<F>,
+    pub memory: Vec<u8>,
+    pub frame_buffer: Vec<F>,
+    /// Sponge state of the input hash
+    pub input_state: [u64; KECCAK_STATE_SIZE],
+}
+
+impl<F: PrimeField32> Default for SegmentState<F> {
+    fn default() -> Self {
+        let state = SimpleState::default();
+        Self {
+            cpu: CpuCols {
+                program_counter: F::from_canonical_u16(state.program_counter),
+                ..Default::default()
+            },
+            memory: state.memory.to_vec(),
+            frame_buffer: vec![F::zero(); FRAME_BUFFER_SIZE],
+            input_state: [0; KECCAK_STATE_SIZE],
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct PartialMachineTrace<F: PrimeField32> {
+    pub start: SegmentState<F>,
     pub cpu: Vec<CpuCols<F>>,
     pub clear: Vec<ClearCols<F>>,
     pub draw: Vec<DrawCols<F>>,
@@ -48,92 +95,254 @@ pub struct PartialMachineTrace<F: PrimeField32> {
     // range_trace: Vec::default(),
     pub memory: Vec<MemoryEventLike<F>>,
     pub frame_buffer: Vec<MemoryEventLike<F>>,
-    // TODO: Change to running hash
-    // pub inputs: Vec<(u64, InputKind)>,
 }
 
 impl<F: PrimeField32> PartialMachineTrace<F> {
+    /// Input log as `(clk, key, kind)` events in the order they are hashed.
+    pub fn inputs(&self) -> Vec<(u64, u8, u8)> {
+        self.keypad
+            .iter()
+            .filter(|row| row.is_real == F::one())
+            .map(|row| {
+                (
+                    row.clk.as_canonical_u64(),
+                    row.index.as_canonical_u32() as u8,
+                    row.value.as_canonical_u32() as u8,
+                )
+            })
+            .collect()
+    }
+
+    /// Sponge state of the input hash after absorbing the input log of the segment.
+    pub fn end_input_state(&self) -> [u64; KECCAK_STATE_SIZE] {
+        let (_, state) = input_preimages(self.start.input_state, self.inputs());
+        state
+    }
+
+    pub fn public_values(&self) -> Vec<F> {
+        let mut values = vec![F::zero(); PublicValues::<F>::num_cols()];
+        let public_values: &mut PublicValues<F> = values.as_mut_slice().borrow_mut();
+
+        let start = &self.start.cpu;
+        public_values.start_clk = start.clk;
+        public_values.start_program_counter = start.program_counter;
+        public_values.start_registers = start.registers;
+        public_values.start_index_register = start.index_register;
+        public_values.start_stack = start.stack;
+        public_values.start_stack_pointer = start.stack_pointer;
+        public_values.start_delay_timer = start.delay_timer;
+        public_values.start_sound_timer = start.sound_timer;
+        public_values.start_keypad = start.keypad;
+        public_values.start_random_state = start.random_state;
+        public_values.start_wait_key = start.wait_key;
+        public_values.start_input_state =
+            sponge_limbs(&self.start.input_state).map(F::from_canonical_u16);
+        for (limb, value) in public_values
+            .start_memory_digest
+            .iter_mut()
+            .zip(memory_digest(&self.start.memory))
+        {
+            *limb = F::from_canonical_u16(value);
+        }
+        public_values
+            .start_frame_buffer
+            .copy_from_slice(&self.start.frame_buffer);
+
+        if let Some(end) = self.cpu.last() {
+            public_values.end_clk = end.clk;
+            public_values.end_program_counter = end.program_counter;
+            public_values.end_registers = end.registers;
+            public_values.end_index_register = end.index_register;
+            public_values.end_stack = end.stack;
+            public_values.end_stack_pointer = end.stack_pointer;
+            public_values.end_delay_timer = end.delay_timer;
+            public_values.end_sound_timer = end.sound_timer;
+            public_values.end_keypad = end.keypad;
+            public_values.end_random_state = end.random_state;
+            public_values.end_wait_key = end.wait_key;
+        }
+        public_values.end_input_state =
+            sponge_limbs(&self.end_input_state()).map(F::from_canonical_u16);
+
+        for (limb, value) in public_values
+            .end_memory_digest
+            .iter_mut()
+            .zip(memory_digest(&self.final_memory()))
+        {
+            *limb = F::from_canonical_u16(value);
+        }
+
+        public_values
+            .end_frame_buffer
+            .copy_from_slice(&self.start.frame_buffer);
+        for event in self.frame_buffer.iter() {
+            public_values.end_frame_buffer[event.address.as_canonical_u32() as usize] = event.value;
+        }
+
+        values
+    }
+
+    /// Memory image at the end of the segment.
+    pub fn final_memory(&self) -> Vec<u8> {
+        // Events are recorded in execution order so the last one holds the final value
+        let mut memory = self.start.memory.clone();
+        for event in self
+            .memory
+            .iter()
+            .filter(|event| event.is_read == F::zero())
+        {
+            memory[event.address.as_canonical_u32() as usize] =
+                event.value.as_canonical_u32() as u8;
+        }
+        memory
+    }
+
     pub fn get_trace_matrices(mut self) -> Vec<Option<RowMajorMatrix<F>>> {
+        let final_memory = self.final_memory();
         let mut range_counts = BTreeMap::new();
-        let mut first_memory_reads = BTreeSet::new();
+        let mut accessed_addresses = BTreeSet::new();
 
+        // Each accessed address starts with its value at the segment start followed by its
+        // accesses in order, sorting is stable so they stay in execution order
         self.memory.sort_by_key(|event| event.address);
-        let mut memory_trace = vec![MemoryCols::default(); self.memory.len()];
-        for (i, event) in self.memory.iter().enumerate() {
-            memory_trace[i].addr = event.address;
-            memory_trace[i].clk = event.clk;
-            memory_trace[i].value = event.value;
-
-            memory_trace[i].is_read = event.is_read;
-            memory_trace[i].is_write = F::one() - event.is_read;
-
-            let diff = if i > 0 {
-                if memory_trace[i].addr == memory_trace[i - 1].addr {
-                    memory_trace[i].addr_unchanged = F::one();
-                    memory_trace[i].clk - memory_trace[i - 1].clk
-                } else {
-                    memory_trace[i].addr - memory_trace[i - 1].addr - F::one()
-                }
-            } else {
-                F::zero()
+        let mut memory_events = self.memory.iter().peekable();
+        let mut memory_trace: Vec<MemoryCols<F>> = Vec::new();
+        while let Some(addr) = memory_events.peek().map(|event| event.address) {
+            let mut diff = match memory_trace.last() {
+                Some(prev) => addr - prev.addr - F::one(),
+                None => F::zero(),
+            };
+            let mut row = MemoryCols {
+                addr,
+                value: F::from_canonical_u8(self.start.memory[addr.as_canonical_u32() as usize]),
+                is_init: F::one(),
+                ..Default::default()
             };
+            accessed_addresses.insert(addr);
+
+            loop {
+                let diff_limb_lo = F::from_canonical_u32(diff.as_canonical_u32() % (1 << 8));
+                let diff_limb_hi = F::from_canonical_u32((diff.as_canonical_u32() >> 8) % (1 << 8));
+                row.diff_limb_lo = diff_limb_lo;
+                row.diff_limb_hi = diff_limb_hi;
+                memory_trace.push(row.clone());
+
+                range_counts
+                    .entry(diff_limb_lo)
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
+                range_counts
+                    .entry(diff_limb_hi)
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
+
+                let Some(event) = memory_events.next_if(|event| event.address == addr) else {
+                    break;
+                };
+                diff = if row.is_init == F::one() {
+                    F::zero()
+                } else {
+                    event.clk - row.clk
+                };
+                row.clk = event.clk;
+                row.value = event.value;
+                row.is_init = F::zero();
+                row.is_read = event.is_read;
+                row.is_write = F::one() - event.is_read;
+                row.addr_unchanged = F::one();
+            }
+            memory_trace.last_mut().unwrap().is_final = F::one();
+        }
 
-            if event.is_read == F::one() && (i == 0 || memory_trace[i].addr_unchanged == F::zero())
+        for row in self.cpu.iter() {
+            if row.is_real == F::one() {
+                range_counts
+                    .entry(row.n * F::from_canonical_u32(1 << NIBBLE_BITS))
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
+            }
+            if row.is_store_bcd == F::one() {
+                for digit in [row.vx_bcd0, row.vx_bcd1, row.vx_bcd2] {
+                    for value in [digit, digit + F::from_canonical_u32(BCD_DIGIT_OFFSET)] {
+                        range_counts
+                            .entry(value)
+                            .and_modify(|count| *count += F::one())
+                            .or_insert(F::one());
+                    }
+                }
+            }
+            if row.is_add + row.is_add_xy + row.is_sub_xy + row.is_sub_yx + row.is_random
+                == F::one()
             {
-                first_memory_reads.insert(event.address);
-                memory_trace[i].is_first_read = F::one();
+                range_counts
+                    .entry(row.alu_result)
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
             }
+        }
 
-            let diff_limb_lo = F::from_canonical_u32(diff.as_canonical_u32() % (1 << 8));
-            let diff_limb_hi = F::from_canonical_u32((diff.as_canonical_u32() >> 8) % (1 << 8));
-
-            memory_trace[i].diff_limb_lo = diff_limb_lo;
-            memory_trace[i].diff_limb_hi = diff_limb_hi;
-
-            range_counts
-                .entry(diff_limb_lo)
-                .and_modify(|count| *count += F::one())
-                .or_insert(F::one());
-            range_counts
-                .entry(diff_limb_hi)
-                .and_modify(|count| *count += F::one())
-                .or_insert(F::one());
+        for row in self.draw.iter() {
+            let mut coordinates = vec![];
+            if row.is_real == F::one() {
+                coordinates.push((row.x, DISPLAY_WIDTH));
+            }
+            if row.is_first_inner == F::one() {
+                coordinates.push((row.y, DISPLAY_HEIGHT));
+            }
+            for (coordinate, size) in coordinates {
+                let offset = F::from_canonical_usize((1 << WORD_BITS) - size);
+                for value in [coordinate, coordinate + offset] {
+                    range_counts
+                        .entry(value)
+                        .and_modify(|count| *count += F::one())
+                        .or_insert(F::one());
+                }
+            }
         }
 
+        // Each address starts with its value at the segment start followed by its accesses in order
         self.frame_buffer.sort_by_key(|event| event.address);
-        let mut frame_buffer_trace = vec![FrameBufferCols::default(); self.frame_buffer.len()];
-        for (i, event) in self.frame_buffer.iter().enumerate() {
-            frame_buffer_trace[i].addr = event.address;
-            frame_buffer_trace[i].clk = event.clk;
-            frame_buffer_trace[i].value = event.value;
-
-            frame_buffer_trace[i].is_read = event.is_read;
-            frame_buffer_trace[i].is_write = F::one() - event.is_read;
-
-            let diff = if i > 0 {
-                if frame_buffer_trace[i].addr == frame_buffer_trace[i - 1].addr {
-                    frame_buffer_trace[i].addr_unchanged = F::one();
-                    frame_buffer_trace[i].clk - frame_buffer_trace[i - 1].clk
+        let mut frame_buffer_events = self.frame_buffer.iter().peekable();
+        let mut frame_buffer_trace =
+            Vec::with_capacity(FRAME_BUFFER_SIZE + self.frame_buffer.len());
+        for addr in 0..FRAME_BUFFER_SIZE {
+            let mut row = FrameBufferCols::default();
+            row.addr = F::from_canonical_usize(addr);
+            row.value = self.start.frame_buffer[addr];
+            row.is_init = F::one();
+            row.x_sel[addr % DISPLAY_WIDTH] = F::one();
+            row.y_sel[addr / DISPLAY_WIDTH] = F::one();
+            frame_buffer_trace.push(row.clone());
+
+            while let Some(event) = frame_buffer_events.next_if(|event| event.address == row.addr) {
+                let diff = if row.is_init == F::one() {
+                    F::zero()
                 } else {
-                    frame_buffer_trace[i].addr - frame_buffer_trace[i - 1].addr - F::one()
-                }
-            } else {
-                F::zero()
-            };
-            let diff_limb_lo = F::from_canonical_u32(diff.as_canonical_u32() % (1 << 8));
-            let diff_limb_hi = F::from_canonical_u32((diff.as_canonical_u32() >> 8) % (1 << 8));
-
-            frame_buffer_trace[i].diff_limb_lo = diff_limb_lo;
-            frame_buffer_trace[i].diff_limb_hi = diff_limb_hi;
-
-            range_counts
-                .entry(diff_limb_lo)
-                .and_modify(|count| *count += F::one())
-                .or_insert(F::one());
-            range_counts
-                .entry(diff_limb_hi)
-                .and_modify(|count| *count += F::one())
-                .or_insert(F::one());
+                    event.clk - row.clk
+                };
+                let diff_limb_lo = F::from_canonical_u32(diff.as_canonical_u32() % (1 << 8));
+                let diff_limb_hi = F::from_canonical_u32((diff.as_canonical_u32() >> 8) % (1 << 8));
+
+                row.clk = event.clk;
+                row.value = event.value;
+                row.is_init = F::zero();
+                row.is_read = event.is_read;
+                row.is_write = F::one() - event.is_read;
+                row.addr_unchanged = F::one();
+                row.diff_limb_lo = diff_limb_lo;
+                row.diff_limb_hi = diff_limb_hi;
+                frame_buffer_trace.push(row.clone());
+
+                range_counts
+                    .entry(diff_limb_lo)
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
+                range_counts
+                    .entry(diff_limb_hi)
+                    .and_modify(|count| *count += F::one())
+                    .or_insert(F::one());
+            }
+            frame_buffer_trace.last_mut().unwrap().is_final = F::one();
         }
 
         let range_trace = (0..(1 << 8))
@@ -147,7 +356,104 @@ impl<F: PrimeField32> PartialMachineTrace<F> {
             .collect_vec();
         let memory_start_trace = (0..MEMORY_SIZE)
             .map(|n| MemoryStartCols {
-                mult: F::from_bool(first_memory_reads.contains(&F::from_canonical_usize(n))),
+                mult: F::from_bool(accessed_addresses.contains(&F::from_canonical_usize(n))),
+            })
+            .collect_vec();
+
+        let inputs = self.inputs();
+        let (mut preimages, _) = input_preimages(self.start.input_state, inputs.iter().copied());
+        let num_perms = preimages.len();
+        // Without events the trace is a single permutation that isn't real
+        if preimages.is_empty() {
+            preimages.push([0; KECCAK_STATE_SIZE]);
+        }
+        let keccak_matrix = generate_trace_rows::<F>(preimages);
+        let hash_trace = (0..keccak_matrix.height())
+            .map(|row| {
+                let perm = row / NUM_ROUNDS;
+                let step = row % NUM_ROUNDS;
+                let mut cols = HashCols::default();
+                if perm < num_perms {
+                    let (clk, key, kind) = inputs[perm];
+                    cols.is_real = F::one();
+                    cols.is_absorb = F::from_bool(step == 0);
+                    cols.is_final = F::from_bool(perm == num_perms - 1 && step == NUM_ROUNDS - 1);
+                    cols.clk = F::from_canonical_u64(clk);
+                    cols.key = F::from_canonical_u8(key);
+                    cols.kind = F::from_canonical_u8(kind);
+                }
+                cols
+            })
+            .collect_vec();
+        let hash_matrix = Some(with_keccak_cols(
+            keccak_matrix,
+            hash_trace
+                .to_trace_matrix(HashCols::<F>::num_cols())
+                .expect("hash trace is never empty"),
+        ));
+
+        // Each permutation absorbs one lane of its chunk of memory in each of its first rounds
+        let (preimages, _) = memory_preimages(&final_memory);
+        let num_perms = preimages.len();
+        let keccak_matrix = generate_trace_rows::<F>(preimages);
+        let memory_final_trace = (0..keccak_matrix.height())
+            .map(|row| {
+                let perm = row / NUM_ROUNDS;
+                let step = row % NUM_ROUNDS;
+                let mut cols = MemoryFinalCols::default();
+                if perm < num_perms {
+                    let addr = perm * MEMORY_CHUNK_SIZE + step.min(MEMORY_CHUNK_LANES) * LANE_BYTES;
+                    cols.is_real = F::one();
+                    cols.is_digest = F::from_bool(perm == num_perms - 1 && step == NUM_ROUNDS - 1);
+                    cols.addr = F::from_canonical_usize(addr);
+                    if step < MEMORY_CHUNK_LANES {
+                        cols.is_absorb = F::one();
+                        for (value, &byte) in cols
+                            .values
+                            .iter_mut()
+                            .zip(&final_memory[addr..addr + LANE_BYTES])
+                        {
+                            *value = F::from_canonical_u8(byte);
+                        }
+                    }
+                }
+                cols
+            })
+            .collect_vec();
+        let memory_final_matrix = Some(with_keccak_cols(
+            keccak_matrix,
+            memory_final_trace
+                .to_trace_matrix(MemoryFinalCols::<F>::num_cols())
+                .expect("memory final trace is never empty"),
+        ));
+
+        let random_trace = self
+            .cpu
+            .iter()
+            .filter(|row| row.is_random == F::one())
+            .map(|row| {
+                let state = row.prev_random_state.as_canonical_u32();
+                let nn = row.nn.as_canonical_u32();
+                let mut cols = RandomCols {
+                    is_real: F::one(),
+                    value: row.alu_result,
+                    ..Default::default()
+                };
+                for (i, bit) in cols.state_bits.iter_mut().enumerate() {
+                    *bit = F::from_canonical_u32((state >> i) & 1);
+                }
+                for (i, bit) in cols.nn_bits.iter_mut().enumerate() {
+                    *bit = F::from_canonical_u32((nn >> i) & 1);
+                }
+                for i in 0..LFSR_SHIFTS_PER_BYTE {
+                    let sum = LFSR_TAPS
+                        .iter()
+                        .map(|&tap| (state >> (i + tap)) & 1)
+                        .sum::<u32>();
+                    cols.feedback_bits[i] = F::from_canonical_u32(sum & 1);
+                    cols.feedback_carry[i] = F::from_canonical_u32(sum >> 1);
+                }
+                cols
             })
             .collect_vec();
 
@@ -161,6 +467,7 @@ impl<F: PrimeField32> PartialMachineTrace<F> {
         let range_matrix = range_trace.to_trace_matrix(RangeCols::<F>::num_cols());
         let memory_start_matrix =
             memory_start_trace.to_trace_matrix(MemoryStartCols::<F>::num_cols());
+        let random_matrix = random_trace.to_trace_matrix(RandomCols::<F>::num_cols());
 
         vec![
             cpu_matrix,
@@ -171,6 +478,9 @@ impl<F: PrimeField32> PartialMachineTrace<F> {
             frame_buffer_matrix,
             range_matrix,
             memory_start_matrix,
+            memory_final_matrix,
+            hash_matrix,
+            random_matrix,
         ]
     }
 }
@@ -184,8 +494,6 @@ pub struct IncrementalMachineTrace<F: PrimeField32> {
     // range_trace: IncrementalTrace::default(),
     pub memory: Vec<MemoryEventLike<F>>,
     pub frame_buffer: Vec<MemoryEventLike<F>>,
-    // TODO: Change to running hash
-    // pub inputs: Vec<(u64, InputKind)>,
 }
 
 impl<F: PrimeField32> Default for IncrementalMachineTrace<F> {
@@ -208,40 +516,102 @@ impl<F: PrimeField32> Default for IncrementalMachineTrace<F> {
 // TODO: Derive simple state from traces
 pub struct StarkState<F: PrimeField32> {
     pub state: SimpleState,
+    pub quirks: Quirks,
     pub trace: IncrementalMachineTrace<F>,
+    pub start: SegmentState<F>,
 }
 
 impl<F: PrimeField32> Default for StarkState<F> {
     fn default() -> Self {
         Self {
             state: SimpleState::default(),
+            quirks: Quirks::default(),
             trace: IncrementalMachineTrace::default(),
+            start: SegmentState::default(),
         }
     }
 }
 
 impl<F: PrimeField32> StarkState<F> {
-    pub fn finalize_trace(&mut self) -> PartialMachineTrace<F> {
-        // TODO: Remove clones
-        let cpu = self.trace.cpu.trace.clone();
-        let clear = self.trace.clear.trace.clone();
-        let draw = self.trace.draw.trace.clone();
-        let keypad = self.trace.keypad.trace.clone();
-
-        PartialMachineTrace {
+    /// Initial state with the CXNN generator seeded with `random_seed`.
+    pub fn new(random_seed: u16) -> Self {
+        let mut state = Self::default();
+        let random_state = F::from_canonical_u16(random_seed);
+        state.start.cpu.random_state = random_state;
+        state.trace.cpu.curr_row.prev_random_state = random_state;
+        state.trace.cpu.curr_row.random_state = random_state;
+        state
+    }
+
+    /// Whether no instruction was executed since the last segment ended.
+    pub fn is_segment_empty(&self) -> bool {
+        self.trace.cpu.trace.is_empty()
+    }
+
+    /// Ends the current segment and returns its trace. The next segment starts from the state
+    /// after the last executed instruction.
+    pub fn finalize_trace(&mut self) -> Result<PartialMachineTrace<F>, Chip8Error> {
+        let trace = mem::take(&mut self.trace);
+        let mut cpu = trace.cpu.trace;
+
+        // State after the last executed instruction
+        let curr_row = trace.cpu.curr_row;
+        let mut final_row = CpuCols {
+            is_final: F::one(),
+            clk: curr_row.clk,
+            program_counter: curr_row.program_counter,
+            registers: curr_row.registers,
+            index_register: curr_row.index_register,
+            stack: curr_row.stack,
+            stack_pointer: curr_row.stack_pointer,
+            delay_timer: curr_row.delay_timer,
+            sound_timer: curr_row.sound_timer,
+            keypad: curr_row.keypad,
+            prev_random_state: curr_row.random_state,
+            random_state: curr_row.random_state,
+            prev_wait_key: curr_row.wait_key,
+            wait_key: curr_row.wait_key,
+            ..Default::default()
+        };
+        set_timer_cols(&mut final_row);
+        cpu.push(final_row.clone());
+
+        // The next segment continues from the in-progress row
+        self.trace.cpu.curr_row = curr_row;
+
+        let frame_buffer = self
+            .state
+            .frame_buffer
+            .checked_read()?
+            .plane(0)
+            .map(F::from_bool)
+            .collect();
+        let partial_trace = PartialMachineTrace {
+            start: mem::take(&mut self.start),
             cpu,
-            clear,
-            draw,
-            keypad,
-            memory: self.trace.memory.clone(),
-            frame_buffer: self.trace.frame_buffer.clone(),
-        }
+            clear: trace.clear.trace,
+            draw: trace.draw.trace,
+            keypad: trace.keypad.trace,
+            memory: trace.memory,
+            frame_buffer: trace.frame_buffer,
+        };
+        // The input hash of the next segment continues from the sponge state of this one
+        self.start = SegmentState {
+            cpu: final_row,
+            memory: self.state.memory.to_vec(),
+            frame_buffer,
+            input_state: partial_trace.end_input_state(),
+        };
+
+        Ok(partial_trace)
     }
 }
 
 impl<F: PrimeField32> State for StarkState<F> {
     fn load_rom(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
-        self.state.load_rom(bytes)
+        self.state.load_rom(bytes)?;
+        self.start.memory = self.state.memory.to_vec();
+        Ok(())
     }
 
     fn clk(&self) -> Result<u64, Chip8Error> {
@@ -256,10 +626,14 @@ impl<F: PrimeField32> State for StarkState<F> {
         self.state.sound_timer_ptr()
     }
 
-    fn frame_buffer_ptr(&self) -> Arc<RwLock<[[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT]>> {
+    fn frame_buffer_ptr(&self) -> Arc<RwLock<FrameBuffer>> {
         self.state.frame_buffer_ptr()
     }
 
+    fn audio_pattern_ptr(&self) -> Arc<RwLock<AudioPattern>> {
+        self.state.audio_pattern_ptr()
+    }
+
     fn program_counter(&self) -> Address {
         self.state.program_counter()
     }
@@ -299,8 +673,16 @@ impl<F: PrimeField32> State for StarkState<F> {
         self.state.key(index)
     }
 
-    fn frame_buffer(&mut self, y: usize, x: usize) -> Result<bool, Chip8Error> {
-        let value = self.state.frame_buffer(y, x)?;
+    fn key_pressed(&self, index: Word) -> bool {
+        self.state.key_pressed(index)
+    }
+
+    fn wait_key(&self) -> Option<Word> {
+        self.state.wait_key()
+    }
+
+    fn frame_buffer(&mut self, plane: usize, y: usize, x: usize) -> Result<bool, Chip8Error> {
+        let value = self.state.frame_buffer(plane, y, x)?;
 
         let clk = self.clk()?;
         let addr = y * DISPLAY_WIDTH + x;
@@ -315,7 +697,51 @@ impl<F: PrimeField32> State for StarkState<F> {
         Ok(value)
     }
 
-    fn set_frame_buffer(&mut self, y: usize, x: usize, bit: bool) -> Result<(), Chip8Error> {
+    // The SUPER-CHIP and XO-CHIP state below is not proven. `StarkCpu` only decodes CHIP-8
+    // instructions, so it never changes and only the first plane is drawn.
+    fn is_hires(&self) -> bool {
+        self.state.is_hires()
+    }
+
+    fn rpl_flag(&self, index: Word) -> Word {
+        self.state.rpl_flag(index)
+    }
+
+    fn planes(&self) -> Word {
+        self.state.planes()
+    }
+
+    fn set_hires(&mut self, hires: bool) -> Result<(), Chip8Error> {
+        self.state.set_hires(hires)
+    }
+
+    fn set_rpl_flag(&mut self, index: Word, value: Word) {
+        self.state.set_rpl_flag(index, value)
+    }
+
+    fn set_planes(&mut self, planes: Word) {
+        self.state.set_planes(planes)
+    }
+
+    fn set_audio_pattern(&mut self, pattern: [Word; AUDIO_PATTERN_SIZE]) -> Result<(), Chip8Error> {
+        self.state.set_audio_pattern(pattern)
+    }
+
+    fn set_pitch(&mut self, pitch: Word) -> Result<(), Chip8Error> {
+        self.state.set_pitch(pitch)
+    }
+
+    fn scroll_framebuffer(&mut self, dx: isize, dy: isize) -> Result<(), Chip8Error> {
+        self.state.scroll_framebuffer(dx, dy)
+    }
+
+    fn set_frame_buffer(
+        &mut self,
+        plane: usize,
+        y: usize,
+        x: usize,
+        bit: bool,
+    ) -> Result<(), Chip8Error> {
         let clk = self.clk()?;
         let addr = y * DISPLAY_WIDTH + x;
         let event = MemoryEventLike {
@@ -326,7 +752,7 @@ impl<F: PrimeField32> State for StarkState<F> {
         };
         self.trace.frame_buffer.push(event);
 
-        self.state.set_frame_buffer(y, x, bit)
+        self.state.set_frame_buffer(plane, y, x, bit)
     }
 
     fn set_program_counter(&mut self, pc: Address) {
@@ -360,6 +786,7 @@ impl<F: PrimeField32> State for StarkState<F> {
     fn set_register(&mut self, index: Word, value: Word) {
         let curr_row = &mut self.trace.cpu.curr_row;
         curr_row.registers[index as usize] = F::from_canonical_u8(value);
+        curr_row.alu_result = F::from_canonical_u8(value);
 
         self.state.set_register(index, value)
     }
@@ -367,6 +794,7 @@ impl<F: PrimeField32> State for StarkState<F> {
     fn set_flag_register(&mut self, flag: bool) {
         let curr_row = &mut self.trace.cpu.curr_row;
         curr_row.registers[FLAG_REGISTER] = F::from_bool(flag);
+        curr_row.alu_flag = F::from_bool(flag);
 
         self.state.set_flag_register(flag)
     }
@@ -385,8 +813,21 @@ impl<F: PrimeField32> State for StarkState<F> {
     }
 
     fn set_key(&mut self, key: Key, kind: InputKind) {
-        self.trace.cpu.curr_row.keypad[key as usize] = F::from_bool(kind == InputKind::Press);
+        // Repeated presses and releases don't change the key state and are neither logged nor
+        // hashed into the input digest
+        if self.state.key(key as u8) == (kind == InputKind::Press) {
+            return;
+        }
+
+        let curr_row = &mut self.trace.cpu.curr_row;
+        curr_row.keypad[key as usize] = F::from_bool(kind == InputKind::Press);
+        curr_row.keypad_diff[key as usize] += match kind {
+            InputKind::Press => F::one(),
+            InputKind::Release => F::neg_one(),
+        };
 
+        self.trace.keypad.curr_row.is_real = F::one();
+        self.trace.keypad.curr_row.clk = self.trace.cpu.curr_row.clk;
         self.trace.keypad.curr_row.index = F::from_canonical_usize(key as usize);
         self.trace.keypad.curr_row.value = F::from_bool(kind == InputKind::Press);
         self.trace.keypad.add_curr_row_to_trace();
@@ -394,6 +835,13 @@ impl<F: PrimeField32> State for StarkState<F> {
         self.state.set_key(key, kind)
     }
 
+    fn set_wait_key(&mut self, key: Option<Word>) {
+        let curr_row = &mut self.trace.cpu.curr_row;
+        curr_row.wait_key = key.map_or(F::zero(), |key| F::from_canonical_u8(key + 1));
+
+        self.state.set_wait_key(key)
+    }
+
     fn clear_framebuffer(&mut self) -> Result<(), Chip8Error> {
         let clk = self.clk()?;
         for y in 0..DISPLAY_HEIGHT {
@@ -424,40 +872,28 @@ impl<F: PrimeField32> State for StarkState<F> {
         self.state.clear_framebuffer()
     }
 
-    fn push_stack(&mut self, addr: Address) {
+    fn push_stack(&mut self, addr: Address) -> Result<(), Chip8Error> {
+        let slot = self.state.stack_pointer as usize;
+        let return_address = self.state.program_counter;
+        self.state.push_stack(addr)?;
+
         let curr_row = &mut self.trace.cpu.curr_row;
-        let next_row = &mut self.trace.cpu.next_row;
-        curr_row.stack[self.state.stack_pointer as usize] =
-            F::from_canonical_u16(self.state.program_counter);
+        curr_row.stack[slot] = F::from_canonical_u16(return_address);
         curr_row.stack_pointer += F::one();
-        // TODO: Move this to a helper function
-        for i in 0..STACK_DEPTH {
-            if i == self.state.stack_pointer as usize + 1 {
-                curr_row.stack_pointer_sel[i] = F::from_bool(true);
-            } else {
-                curr_row.stack_pointer_sel[i] = F::from_bool(false);
-            }
-        }
-        next_row.program_counter = F::from_canonical_u16(addr);
-
-        self.state.push_stack(addr)
+        curr_row.stack_pointer_sel[slot] = F::one();
+        self.trace.cpu.next_row.program_counter = F::from_canonical_u16(addr);
+        Ok(())
     }
 
-    fn pop_stack(&mut self) {
+    fn pop_stack(&mut self) -> Result<(), Chip8Error> {
+        self.state.pop_stack()?;
+        let slot = self.state.stack_pointer as usize;
+
         let curr_row = &mut self.trace.cpu.curr_row;
-        let next_row = &mut self.trace.cpu.next_row;
         curr_row.stack_pointer -= F::one();
-        for i in 0..STACK_DEPTH {
-            if i == self.state.stack_pointer as usize - 1 {
-                curr_row.stack_pointer_sel[i] = F::from_bool(true);
-            } else {
-                curr_row.stack_pointer_sel[i] = F::from_bool(false);
-            }
-        }
-        next_row.program_counter =
-            F::from_canonical_u16(self.state.stack[self.state.stack_pointer as usize - 1]);
-
-        self.state.pop_stack()
+        curr_row.stack_pointer_sel[slot] = F::one();
+        self.trace.cpu.next_row.program_counter = F::from_canonical_u16(self.state.stack[slot]);
+        Ok(())
     }
 
     fn increment_program_counter(&mut self) {
@@ -472,13 +908,15 @@ impl<F: PrimeField32> State for StarkState<F> {
         let next_row = &mut self.trace.cpu.next_row;
         next_row.clk = curr_row.clk + F::one();
 
-        self.trace.cpu.add_curr_row_to_trace();
+        self.trace.cpu.add_curr_row_to_trace(self.quirks);
 
         self.state.increment_clk()
     }
 
     fn decrement_delay_timer(&mut self) {
         let curr_row = &mut self.trace.cpu.curr_row;
+        curr_row.delay_timer_inv = curr_row.delay_timer.inverse();
+        curr_row.is_delay_timer_nonzero = F::one();
         curr_row.delay_timer -= F::one();
 
         self.state.decrement_delay_timer()
@@ -486,6 +924,8 @@ impl<F: PrimeField32> State for StarkState<F> {
 
     fn decrement_sound_timer(&mut self) -> Result<(), Chip8Error> {
         let curr_row = &mut self.trace.cpu.curr_row;
+        curr_row.sound_timer_inv = curr_row.sound_timer.inverse();
+        curr_row.is_sound_timer_nonzero = F::one();
         curr_row.sound_timer -= F::one();
 
         self.state.decrement_sound_timer()
@@ -493,7 +933,8 @@ impl<F: PrimeField32> State for StarkState<F> {
 }
 
 impl<F: PrimeField32> IncrementalTrace<CpuCols<F>> {
-    pub fn add_curr_row_to_trace(&mut self) {
+    /// Fills the operand columns from the registers before the instruction executes.
+    pub fn set_operands(&mut self) {
         let vx = self
             .curr_row
             .x_sel
@@ -511,6 +952,12 @@ impl<F: PrimeField32> IncrementalTrace<CpuCols<F>> {
 
         self.curr_row.vx = vx;
         self.curr_row.vy = vy;
+        // Instructions that write vx overwrite this
+        self.curr_row.alu_result = vx;
+        for i in 0..WORD_BITS {
+            self.curr_row.vx_bits[i] = F::from_canonical_u32((vx.as_canonical_u32() >> i) & 1);
+            self.curr_row.vy_bits[i] = F::from_canonical_u32((vy.as_canonical_u32() >> i) & 1);
+        }
         for i in 0..NUM_KEYS {
             self.curr_row.vx_sel[i] = F::from_bool(vx == F::from_canonical_usize(i));
         }
@@ -523,20 +970,105 @@ impl<F: PrimeField32> IncrementalTrace<CpuCols<F>> {
         self.curr_row.diff_vx_vy_inv = (vx - vy).try_inverse().unwrap_or_default();
         self.curr_row.is_equal_vx_vy = F::from_bool(vx == vy);
 
+        self.curr_row.n_inv = self.curr_row.n.try_inverse().unwrap_or_default();
+    }
+
+    fn set_wait_key_cols(&mut self) {
+        let row = &mut self.curr_row;
+        if row.is_wait_key_press != F::one() {
+            return;
+        }
+        let prev_wait_key = row.prev_wait_key.as_canonical_u32() as usize;
+        if prev_wait_key > 0 {
+            let key = prev_wait_key - 1;
+            row.is_wait_key_held = F::one();
+            row.wait_key_sel[key] = F::one();
+            row.is_wait_key_done = F::one() - row.keypad[key];
+        } else {
+            row.is_wait_key_idle = F::one();
+            if let Some(key) = (0..NUM_KEYS).find(|&i| row.keypad_diff[i] == F::one()) {
+                row.wait_key_sel[key] = F::one();
+            }
+        }
+    }
+
+    fn set_draw_cols(&mut self, quirks: Quirks) {
+        let row = &mut self.curr_row;
+        if quirks.display_wait {
+            row.is_draw_wait = row.is_draw * (F::one() - row.is_timer_tick);
+        }
+        row.is_draw_nonempty = (row.is_draw - row.is_draw_wait) * row.n * row.n_inv;
+    }
+
+    fn set_write_sel(&mut self, quirks: Quirks) {
+        let row = &mut self.curr_row;
+        let is_logic = row.is_or + row.is_and + row.is_xor;
+        let mut is_flag =
+            row.is_add_xy + row.is_sub_xy + row.is_sub_yx + row.is_shift_left + row.is_shift_right;
+        if quirks.vf_reset {
+            is_flag += is_logic;
+        }
+        let is_write_vx = row.is_load
+            + row.is_add
+            + row.is_move
+            + is_logic
+            + is_flag
+            + row.is_random
+            + row.is_load_delay
+            + row.is_wait_key_done;
+        for i in 0..NUM_REGISTERS {
+            row.write_sel[i] = row.x_sel[i] * is_write_vx + row.lte_x_sel[i] * row.is_load_memory;
+            if i == FLAG_REGISTER {
+                row.write_sel[i] +=
+                    (F::one() - row.x_sel[i]) * is_flag + row.is_draw - row.is_draw_wait;
+            }
+        }
+    }
+
+    pub fn add_curr_row_to_trace(&mut self, quirks: Quirks) {
+        set_timer_cols(&mut self.curr_row);
+        self.set_wait_key_cols();
+        self.set_draw_cols(quirks);
+        self.set_write_sel(quirks);
         self.trace.push(self.curr_row.clone());
         // Copy state
         self.next_row.registers = self.curr_row.registers;
         self.next_row.index_register = self.curr_row.index_register;
         self.next_row.stack = self.curr_row.stack;
         self.next_row.stack_pointer = self.curr_row.stack_pointer;
+        self.next_row.delay_timer = self.curr_row.delay_timer;
+        self.next_row.sound_timer = self.curr_row.sound_timer;
         self.next_row.keypad = self.curr_row.keypad;
-        self.next_row.stack_pointer_sel = self.curr_row.stack_pointer_sel;
+        self.next_row.prev_random_state = self.curr_row.random_state;
+        self.next_row.random_state = self.curr_row.random_state;
+        self.next_row.prev_wait_key = self.curr_row.wait_key;
+        self.next_row.wait_key = self.curr_row.wait_key;
 
         self.curr_row = self.next_row.clone();
         self.next_row = CpuCols::default();
     }
 }
 
+/// Fills the timer counter and, unless a tick already did, whether the timers are non-zero.
+fn set_timer_cols<F: PrimeField32>(row: &mut CpuCols<F>) {
+    let timer_counter = row.clk.as_canonical_u64() % TICKS_PER_TIMER;
+    row.timer_counter = F::from_canonical_u64(timer_counter);
+    row.timer_counter_diff_inv = (row.timer_counter - F::from_canonical_u64(TICKS_PER_TIMER - 1))
+        .try_inverse()
+        .unwrap_or_default();
+    row.is_timer_counter_max = F::from_bool(timer_counter == TICKS_PER_TIMER - 1);
+    row.is_timer_tick = F::from_bool(timer_counter == 0);
+
+    if row.is_delay_timer_nonzero.is_zero() {
+        row.delay_timer_inv = row.delay_timer.try_inverse().unwrap_or_default();
+        row.is_delay_timer_nonzero = F::from_bool(!row.delay_timer.is_zero());
+    }
+    if row.is_sound_timer_nonzero.is_zero() {
+        row.sound_timer_inv = row.sound_timer.try_inverse().unwrap_or_default();
+        row.is_sound_timer_nonzero = F::from_bool(!row.sound_timer.is_zero());
+    }
+}
+
 impl<F: PrimeField32> IncrementalTrace<KeypadCols<F>> {
     pub fn add_curr_row_to_trace(&mut self) {
         self.trace.push(self.curr_row.clone());
@@ -555,6 +1087,9 @@ impl<F: PrimeField32> IncrementalTrace<DrawCols<F>> {
         self.next_row.register_x = self.curr_row.register_x;
         self.next_row.register_y = self.curr_row.register_y;
         self.next_row.index_register = self.curr_row.index_register;
+        self.next_row.n = self.curr_row.n;
+        self.next_row.register_x_bits = self.curr_row.register_x_bits;
+        self.next_row.register_y_bits = self.curr_row.register_y_bits;
 
         self.curr_row = self.next_row.clone();
         self.next_row = DrawCols::default();
@@ -576,6 +1111,21 @@ impl<F: PrimeField32> IncrementalTrace<ClearCols<F>> {
     }
 }
 
+/// Places the columns of a Keccak chip after the permutation columns of each row.
+fn with_keccak_cols<F: PrimeField32>(
+    keccak_matrix: RowMajorMatrix<F>,
+    cols_matrix: RowMajorMatrix<F>,
+) -> RowMajorMatrix<F> {
+    let width = NUM_KECCAK_COLS + cols_matrix.width();
+    let values = keccak_matrix
+        .values
+        .chunks_exact(NUM_KECCAK_COLS)
+        .zip(cols_matrix.values.chunks_exact(cols_matrix.width()))
+        .flat_map(|(keccak_row, row)| keccak_row.iter().chain(row).copied())
+        .collect_vec();
+    RowMajorMatrix::new(values, width)
+}
+
 pub trait ToTraceMatrix<F: PrimeField32> {
     fn to_trace_matrix(&self, num_cols: usize) -> Option<RowMajorMatrix<F>>;
 }
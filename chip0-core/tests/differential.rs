@@ -0,0 +1,258 @@
+//! Differential fuzzing of `SimpleCpu` against `StarkCpu`. Random ROMs and input logs are run on
+//! both CPUs, which must agree on the architectural state after every cycle. The trace recorded by
+//! `StarkCpu` must then satisfy the constraints of every `Chip0Machine` chip, up to the first error
+//! when a case stops early.
+
+use std::collections::VecDeque;
+
+use chip0_core::{
+    config::MyConfig,
+    cpu::StarkCpu,
+    debug::{check_buses, check_constraints},
+    machine::Chip0Machine,
+    prover::DefaultProver,
+    trace::StarkState,
+};
+use chip8_core::{
+    constants::{NUM_KEYS, NUM_REGISTERS, OPCODE_SIZE, PROGRAM_START_ADDRESS},
+    cpu::{Cpu, SimpleCpu},
+    input::{InputEvent, InputKind},
+    keypad::Key,
+    quirks::Quirks,
+    rng::Lfsr,
+    state::{SimpleState, State},
+};
+use itertools::Itertools;
+use p3_field::AbstractField;
+use p3_machine::machine::Machine;
+use p3_uni_stark::Val;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+type F = Val<MyConfig>;
+
+const NUM_CASES: u64 = 16;
+const NUM_CYCLES: u64 = 1000;
+const NUM_INSTRUCTIONS: u16 = 64;
+const NUM_INPUTS: usize = 32;
+const CLK_FREQ: u64 = 500;
+
+const QUIRKS: [&str; 4] = ["none", "vip", "schip", "xochip"];
+
+// Opcodes decoded by `StarkCpu` and the bits filled in at random
+const OPCODES: [(u16, u16); 34] = [
+    (0x00E0, 0x0000),
+    (0x00EE, 0x0000),
+    (0x1000, 0x0FFF),
+    (0x2000, 0x0FFF),
+    (0x3000, 0x0FFF),
+    (0x4000, 0x0FFF),
+    (0x5000, 0x0FF0),
+    (0x6000, 0x0FFF),
+    (0x7000, 0x0FFF),
+    (0x8000, 0x0FF0),
+    (0x8001, 0x0FF0),
+    (0x8002, 0x0FF0),
+    (0x8003, 0x0FF0),
+    (0x8004, 0x0FF0),
+    (0x8005, 0x0FF0),
+    (0x8006, 0x0FF0),
+    (0x8007, 0x0FF0),
+    (0x800E, 0x0FF0),
+    (0x9000, 0x0FF0),
+    (0xA000, 0x0FFF),
+    (0xB000, 0x0FFF),
+    (0xC000, 0x0FFF),
+    (0xD000, 0x0FFF),
+    (0xE09E, 0x0F00),
+    (0xE0A1, 0x0F00),
+    (0xF007, 0x0F00),
+    (0xF00A, 0x0F00),
+    (0xF015, 0x0F00),
+    (0xF018, 0x0F00),
+    (0xF01E, 0x0F00),
+    (0xF029, 0x0F00),
+    (0xF033, 0x0F00),
+    (0xF055, 0x0F00),
+    (0xF065, 0x0F00),
+];
+
+/// ROM of random instructions. Jumps and calls stay inside the ROM so execution mostly does too,
+/// and a trailing self-jump keeps it from running past the end.
+fn random_rom(rng: &mut StdRng) -> Vec<u8> {
+    let end = PROGRAM_START_ADDRESS + OPCODE_SIZE * NUM_INSTRUCTIONS;
+    (0..NUM_INSTRUCTIONS)
+        .map(|_| {
+            let &(opcode, mask) = OPCODES.choose(rng).unwrap();
+            match opcode {
+                0x1000 | 0x2000 => {
+                    opcode
+                        | (PROGRAM_START_ADDRESS + OPCODE_SIZE * rng.gen_range(0..NUM_INSTRUCTIONS))
+                }
+                _ => opcode | (rng.gen::<u16>() & mask),
+            }
+        })
+        .chain([0x1000 | end])
+        .flat_map(u16::to_be_bytes)
+        .collect()
+}
+
+fn random_inputs(rng: &mut StdRng) -> VecDeque<(u64, InputEvent)> {
+    (0..NUM_INPUTS)
+        .map(|_| {
+            let clk = rng.gen_range(0..NUM_CYCLES);
+            let key = char::from_digit(rng.gen_range(0..NUM_KEYS as u32), NUM_KEYS as u32).unwrap();
+            let key = Key::try_from(key.to_ascii_uppercase()).unwrap();
+            let kind = InputKind::try_from(rng.gen_range(0..2)).unwrap();
+            (clk, InputEvent { key, kind })
+        })
+        .sorted_by_key(|(clk, _)| *clk)
+        .collect()
+}
+
+fn stark_cpu(
+    rom: &[u8],
+    random_seed: u16,
+    quirks: Quirks,
+) -> StarkCpu<MyConfig, DefaultProver<MyConfig>> {
+    let mut stark = StarkCpu::new(
+        CLK_FREQ,
+        random_seed,
+        DefaultProver::new(rom.to_vec()),
+        None,
+    )
+    .with_quirks(quirks);
+    stark.state().load_rom(rom).unwrap();
+    stark
+}
+
+fn assert_same_state(case: u64, clk: u64, simple: &SimpleState, stark: &StarkState<F>) {
+    let context = format!("case {case} at clk {clk}");
+    let state = &stark.state;
+    assert_eq!(
+        simple.program_counter, state.program_counter,
+        "pc, {context}"
+    );
+    assert_eq!(simple.registers, state.registers, "registers, {context}");
+    assert_eq!(simple.index_register, state.index_register, "I, {context}");
+    assert_eq!(simple.stack, state.stack, "stack, {context}");
+    assert_eq!(simple.stack_pointer, state.stack_pointer, "sp, {context}");
+    assert_eq!(
+        simple.delay_timer, state.delay_timer,
+        "delay timer, {context}"
+    );
+    assert_eq!(
+        simple.sound_timer().unwrap(),
+        state.sound_timer().unwrap(),
+        "sound timer, {context}"
+    );
+    assert_eq!(simple.keypad, state.keypad, "keypad, {context}");
+    assert_eq!(simple.wait_key, state.wait_key, "wait key, {context}");
+    assert!(simple.memory == state.memory, "memory, {context}");
+    assert!(
+        *simple.frame_buffer.read().unwrap() == *state.frame_buffer.read().unwrap(),
+        "frame buffer, {context}"
+    );
+
+    // The last row holds the state after the instruction and the next one the new pc
+    let row = stark.trace.cpu.trace.last().unwrap();
+    for i in 0..NUM_REGISTERS {
+        assert_eq!(
+            row.registers[i],
+            F::from_canonical_u8(simple.registers[i]),
+            "trace v{i:X}, {context}"
+        );
+    }
+    assert_eq!(
+        row.index_register,
+        F::from_canonical_u16(simple.index_register),
+        "trace I, {context}"
+    );
+    assert_eq!(
+        row.stack_pointer,
+        F::from_canonical_u8(simple.stack_pointer),
+        "trace sp, {context}"
+    );
+    assert_eq!(
+        row.delay_timer,
+        F::from_canonical_u8(simple.delay_timer),
+        "trace delay timer, {context}"
+    );
+    assert_eq!(
+        stark.trace.cpu.curr_row.program_counter,
+        F::from_canonical_u16(simple.program_counter),
+        "trace pc, {context}"
+    );
+}
+
+#[test]
+fn simple_and_stark_cpus_agree() {
+    let mut num_checked = 0;
+    for case in 0..NUM_CASES {
+        let mut rng = StdRng::seed_from_u64(case);
+        let rom = random_rom(&mut rng);
+        let inputs = random_inputs(&mut rng);
+        let quirks: Quirks = QUIRKS.choose(&mut rng).unwrap().parse().unwrap();
+        let random_seed = rng.gen();
+
+        let mut simple = SimpleCpu::new(CLK_FREQ, Lfsr::new(random_seed)).with_quirks(quirks);
+        let mut stark = stark_cpu(&rom, random_seed, quirks);
+        simple.state().load_rom(&rom).unwrap();
+
+        let mut simple_inputs = inputs.clone();
+        let mut stark_inputs = inputs.clone();
+        let mut num_cycles = NUM_CYCLES;
+        for clk in 0..NUM_CYCLES {
+            let simple_res = simple.step(&mut simple_inputs);
+            let stark_res = stark.step(&mut stark_inputs);
+            assert_eq!(
+                format!("{simple_res:?}"),
+                format!("{stark_res:?}"),
+                "result, case {case} at clk {clk}"
+            );
+            if simple_res.is_err() {
+                num_cycles = clk;
+                break;
+            }
+            assert_same_state(case, clk, &simple.state, stark.state());
+        }
+
+        // Both stopped at the same error, which may have left part of the failing cycle in the
+        // trace. Replay the cycles before it to check the trace prefix.
+        if num_cycles < NUM_CYCLES {
+            stark = stark_cpu(&rom, random_seed, quirks);
+            let mut stark_inputs = inputs;
+            for clk in 0..num_cycles {
+                stark
+                    .step(&mut stark_inputs)
+                    .unwrap_or_else(|err| panic!("replay, case {case} at clk {clk}: {err}"));
+            }
+        }
+        if stark.state().is_segment_empty() {
+            continue;
+        }
+
+        let trace = stark.state().finalize_trace().unwrap();
+        let machine = Chip0Machine::new(trace.start.memory.clone(), quirks);
+        let public_values = trace.public_values();
+        let traces = trace.get_trace_matrices();
+        let chips = Machine::<'_, MyConfig>::chips(&machine);
+        let failures = check_constraints(&chips, &traces, &public_values);
+        assert!(
+            failures.is_empty(),
+            "case {case}:\n{}",
+            failures.iter().join("\n")
+        );
+        let unbalanced = check_buses(&chips, &traces);
+        assert!(
+            unbalanced.is_empty(),
+            "case {case}:\n{}",
+            unbalanced.iter().join("\n")
+        );
+        num_checked += 1;
+    }
+    // Guards against most cases erroring before they record anything worth checking
+    assert!(
+        num_checked >= NUM_CASES / 2,
+        "only {num_checked} of {NUM_CASES} cases were checked"
+    );
+}
@@ -1,12 +1,35 @@
-use clap::Parser;
+use chip8_core::{constants::NUM_COLORS, quirks::Quirks, variant::Variant};
+use clap::{Parser, Subcommand};
 use ratatui::style::Color;
 use std::path::PathBuf;
 
+/// Colors 2 to 15 of the XO-CHIP palette
+const DEFAULT_PALETTE: [Color; NUM_COLORS - 2] = [
+    Color::LightRed,
+    Color::Red,
+    Color::LightBlue,
+    Color::Blue,
+    Color::LightGreen,
+    Color::Green,
+    Color::LightYellow,
+    Color::Yellow,
+    Color::LightMagenta,
+    Color::Magenta,
+    Color::LightCyan,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+];
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CmdArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(required = true, value_parser)]
-    pub rom: PathBuf,
+    pub rom: Option<PathBuf>,
 
     #[arg(long = "clock-frequency", default_value_t = 560)]
     pub clk_freq: u64,
@@ -19,8 +42,17 @@ pub struct CmdArgs {
     #[arg(long, default_value_t = false)]
     pub headless: bool,
 
+    /// Instruction set to emulate: chip8, schip or xochip. Only chip8 sessions can be proven.
+    #[arg(long, default_value_t = Variant::Chip8)]
+    pub variant: Variant,
+
+    /// Quirks preset: none, vip, schip or xochip
+    #[arg(long, default_value = "none")]
+    pub quirks: Quirks,
+
+    /// Seed of the CXNN generator, random if not set
     #[arg(long)]
-    pub random_seed: Option<u64>,
+    pub random_seed: Option<u16>,
 
     #[arg(long = "inputs")]
     pub input_file: Option<PathBuf>,
@@ -28,10 +60,43 @@ pub struct CmdArgs {
     #[arg(long, default_value_t = false, requires = "input_file")]
     pub overwrite: bool,
 
+    #[arg(long = "proof")]
+    pub proof_file: Option<PathBuf>,
+
+    /// Check every constraint and bus before proving, logging the failing rows and instructions
+    #[arg(long, default_value_t = false)]
+    pub debug_constraints: bool,
+
+    /// Export the trace matrices of every proven segment as CSV files to this directory
+    #[cfg(feature = "trace-writer")]
+    #[arg(long = "dump-trace")]
+    pub trace_dir: Option<PathBuf>,
+
     #[arg(long = "background", default_value_t = Color::Black, conflicts_with="headless")]
     pub bg_color: Color,
     #[arg(long = "foreground", default_value_t = Color::White, conflicts_with="headless")]
     pub fg_color: Color,
+    /// Comma-separated colors of the other XO-CHIP plane combinations, from 2 (only the second
+    /// plane) to 15 (all four planes)
+    #[arg(
+        long = "palette",
+        value_delimiter = ',',
+        default_values_t = DEFAULT_PALETTE,
+        conflicts_with = "headless"
+    )]
+    pub palette: Vec<Color>,
     #[arg(long = "border", default_value_t = Color::White, conflicts_with="headless")]
     pub border_color: Color,
 }
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Verify a gameplay proof without re-running the emulator
+    Verify {
+        #[arg(required = true, value_parser)]
+        proof: PathBuf,
+        /// Require the session to be played with this CXNN seed
+        #[arg(long)]
+        random_seed: Option<u16>,
+    },
+}
@@ -1,4 +1,4 @@
-use chip8_core::{drivers::AudioDriver, error::Chip8Error};
+use chip8_core::{audio::AudioPattern, drivers::AudioDriver, error::Chip8Error};
 use std::io::{stdout, Write};
 
 const FREQUENCY: u64 = 60;
@@ -18,4 +18,15 @@ impl AudioDriver for TerminalAudio {
             .flush()
             .map_err(|e| Chip8Error::AudioError(e.to_string()))
     }
+
+    // The bell can't play samples, so only silent patterns are told apart
+    fn play(&mut self, pattern: &AudioPattern) -> Result<(), Chip8Error> {
+        if pattern
+            .buffer
+            .is_some_and(|buffer| buffer.iter().any(|&samples| samples != 0))
+        {
+            self.beep()?;
+        }
+        Ok(())
+    }
 }
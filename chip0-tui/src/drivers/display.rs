@@ -1,12 +1,14 @@
 use chip8_core::{
-    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, NUM_COLORS},
     drivers::DisplayDriver,
     error::Chip8Error,
+    frame_buffer::FrameBuffer,
 };
 use ratatui::{
     backend::Backend,
     layout::Rect,
-    style::{Color, Stylize},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
     widgets::{Block, Paragraph},
     Terminal,
 };
@@ -15,8 +17,8 @@ use ratatui::{
 pub struct TerminalDisplay<B: Backend> {
     terminal: Terminal<B>,
     refresh_rate: u64,
-    bg_color: Color,
-    fg_color: Color,
+    /// Color of each pixel value, with bit `i` set if the pixel is on in XO-CHIP plane `i`
+    palette: [Color; NUM_COLORS],
     border_color: Color,
 }
 
@@ -24,15 +26,13 @@ impl<B: Backend> TerminalDisplay<B> {
     pub fn new(
         terminal: Terminal<B>,
         refresh_rate: u64,
-        bg_color: Color,
-        fg_color: Color,
+        palette: [Color; NUM_COLORS],
         border_color: Color,
     ) -> Self {
         Self {
             terminal,
             refresh_rate,
-            bg_color,
-            fg_color,
+            palette,
             border_color,
         }
     }
@@ -43,20 +43,42 @@ impl<B: Backend + Send> DisplayDriver for TerminalDisplay<B> {
         self.refresh_rate
     }
 
-    fn draw(
-        &mut self,
-        frame_buffer: [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
-        cpu_freq: Option<u64>,
-    ) -> Result<(), Chip8Error> {
-        let frame_str = frame_buffer
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|&pixel| if pixel { "██" } else { "  " })
-                    .collect::<String>()
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
+    fn draw(&mut self, frame_buffer: FrameBuffer, cpu_freq: Option<u64>) -> Result<(), Chip8Error> {
+        // Both resolutions fill the same area. A low resolution pixel is two cells wide, while
+        // a cell holds two high resolution pixels on top of each other.
+        let palette = self.palette;
+        let lines: Vec<Line> = if frame_buffer.is_hires() {
+            frame_buffer
+                .rows()
+                .collect::<Vec<_>>()
+                .chunks_exact(2)
+                .map(|rows| {
+                    rows[0]
+                        .iter()
+                        .zip(rows[1])
+                        .map(|(&upper, &lower)| {
+                            Span::styled(
+                                "▀",
+                                Style::new()
+                                    .fg(palette[upper as usize])
+                                    .bg(palette[lower as usize]),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .into()
+                })
+                .collect()
+        } else {
+            frame_buffer
+                .rows()
+                .map(|row| {
+                    row.iter()
+                        .map(|&color| Span::styled("██", Style::new().fg(palette[color as usize])))
+                        .collect::<Vec<_>>()
+                        .into()
+                })
+                .collect()
+        };
 
         let block = Block::bordered()
             .title(format!(
@@ -73,13 +95,7 @@ impl<B: Backend + Send> DisplayDriver for TerminalDisplay<B> {
 
         self.terminal
             .draw(|frame| {
-                frame.render_widget(
-                    Paragraph::new(frame_str)
-                        .bg(self.bg_color)
-                        .fg(self.fg_color)
-                        .block(block),
-                    area,
-                );
+                frame.render_widget(Paragraph::new(lines).bg(palette[0]).block(block), area);
             })
             .map_err(|e| Chip8Error::DisplayError(e.to_string()))?;
 
@@ -2,19 +2,32 @@ mod args;
 mod drivers;
 mod terminal;
 
-use args::CmdArgs;
-use chip0_core::{config::MyConfig, cpu::StarkCpu, prover::DefaultProver};
+use args::{CmdArgs, Command};
+use chip0_core::{
+    config::MyConfig,
+    cpu::StarkCpu,
+    error::Chip0Error,
+    proof::SessionProof,
+    prover::{verify, DefaultProver},
+};
 use chip8_core::{
+    constants::{DISPLAY_WIDTH, NUM_COLORS},
+    cpu::SimpleCpu,
     input::{InputEvent, InputKind},
     keypad::Key,
+    rng::Lfsr,
+    variant::Variant,
     Chip8,
 };
 use clap::Parser;
 use csv::{Reader, Writer, WriterBuilder};
 use drivers::input::CsvRecord;
-use eyre::Result;
-use rand::{random, rngs::StdRng, SeedableRng};
-use std::fs::{self, OpenOptions};
+use eyre::{bail, Result};
+use rand::random;
+use std::{
+    fs::{self, OpenOptions},
+    path::Path,
+};
 use terminal::{restore_terminal, setup_terminal};
 use tracing_forest::{util::LevelFilter, ForestLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
@@ -27,7 +40,70 @@ use crate::drivers::{
 async fn main() -> Result<()> {
     let args = CmdArgs::parse();
 
-    let rom = fs::read(args.rom)?;
+    if let Some(Command::Verify { proof, random_seed }) = &args.command {
+        return verify_proof(proof, *random_seed);
+    }
+    run(args).await
+}
+
+fn verify_proof(path: &Path, random_seed: Option<u16>) -> Result<()> {
+    let proof = SessionProof::<MyConfig>::load(path)?;
+    verify(&proof)?;
+    let outcome = proof.outcome()?;
+    if random_seed.is_some_and(|seed| seed != outcome.random_seed) {
+        return Err(Chip0Error::RandomSeedMismatch.into());
+    }
+
+    let rom_hash: String = outcome
+        .rom_hash
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    println!("Proof verified");
+    println!("ROM hash: {rom_hash}");
+    println!("Random seed: {}", outcome.random_seed);
+    // Limbs are little-endian within each lane, so this is the byte order of the sponge state
+    let input_digest: String = outcome
+        .input_digest
+        .iter()
+        .flat_map(|limb| limb.to_le_bytes())
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    println!("Input digest: {input_digest}");
+    println!("Segments: {}", proof.segments.len());
+    println!("End clk: {}", outcome.end_clk);
+    println!("End PC: 0x{:04X}", outcome.end_program_counter);
+    let registers = outcome
+        .end_registers
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("V{i:X}={v:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!(
+        "End registers: {registers} I={:04X}",
+        outcome.end_index_register
+    );
+    println!("End frame buffer:");
+    for row in outcome.end_frame_buffer.chunks(DISPLAY_WIDTH) {
+        let line: String = row
+            .iter()
+            .map(|&pixel| if pixel != 0 { '#' } else { '.' })
+            .collect();
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+async fn run(args: CmdArgs) -> Result<()> {
+    let rom = fs::read(args.rom.expect("ROM is required without a subcommand"))?;
+    if args.variant != Variant::Chip8 && args.proof_file.is_some() {
+        bail!("Proofs are only supported for the chip8 variant");
+    }
+    if args.palette.len() != NUM_COLORS - 2 {
+        bail!("The palette needs {} colors", NUM_COLORS - 2);
+    }
 
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
@@ -71,11 +147,13 @@ async fn main() -> Result<()> {
     let input_driver = TerminalKeyboardInput::new(input_writer);
     let display_driver = {
         if !args.headless {
+            let mut palette = [args.bg_color; NUM_COLORS];
+            palette[1] = args.fg_color;
+            palette[2..].copy_from_slice(&args.palette);
             Some(TerminalDisplay::new(
                 terminal,
                 args.refresh_rate,
-                args.bg_color,
-                args.fg_color,
+                palette,
                 args.border_color,
             ))
         } else {
@@ -90,19 +168,42 @@ async fn main() -> Result<()> {
         }
     };
 
-    let seeded_rng = StdRng::seed_from_u64(args.random_seed.unwrap_or(random()));
-    let prover = DefaultProver::new(rom.clone());
-    let cpu: StarkCpu<_, MyConfig, _> = StarkCpu::new(args.clk_freq, seeded_rng, prover);
-    let mut chip8 = Chip8::new(cpu, inputs);
-    let res = chip8
-        .load_and_run(
-            rom.as_slice(),
-            args.num_cycles,
-            input_driver,
-            display_driver,
-            audio_driver,
-        )
-        .await;
+    let random_seed = args.random_seed.unwrap_or(random());
+    let res = if args.variant == Variant::Chip8 {
+        let prover = DefaultProver::new(rom.clone())
+            .with_quirks(args.quirks)
+            .with_debug(args.debug_constraints);
+        #[cfg(feature = "trace-writer")]
+        let prover = prover.with_trace_dir(args.trace_dir.clone());
+        let cpu: StarkCpu<MyConfig, _> =
+            StarkCpu::new(args.clk_freq, random_seed, prover, args.proof_file)
+                .with_quirks(args.quirks);
+        let mut chip8 = Chip8::new(cpu, inputs);
+        chip8
+            .load_and_run(
+                rom.as_slice(),
+                args.num_cycles,
+                input_driver,
+                display_driver,
+                audio_driver,
+            )
+            .await
+    } else {
+        // Extended instruction sets are emulated without proving
+        let cpu = SimpleCpu::new(args.clk_freq, Lfsr::new(random_seed))
+            .with_quirks(args.quirks)
+            .with_variant(args.variant);
+        let mut chip8 = Chip8::new(cpu, inputs);
+        chip8
+            .load_and_run(
+                rom.as_slice(),
+                args.num_cycles,
+                input_driver,
+                display_driver,
+                audio_driver,
+            )
+            .await
+    };
 
     restore_terminal(args.headless)?;
     res?;
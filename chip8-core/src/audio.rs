@@ -0,0 +1,29 @@
+use crate::{
+    constants::{AUDIO_PATTERN_SIZE, DEFAULT_PITCH},
+    state::Word,
+};
+
+/// XO-CHIP audio pattern played while the sound timer is active.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioPattern {
+    /// One-bit samples loaded by F002. The buzzer plays its usual tone until then.
+    pub buffer: Option<[Word; AUDIO_PATTERN_SIZE]>,
+    /// Playback rate set by FX3A.
+    pub pitch: Word,
+}
+
+impl Default for AudioPattern {
+    fn default() -> Self {
+        Self {
+            buffer: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+impl AudioPattern {
+    /// Samples played per second, 4000 * 2^((pitch - 64) / 48).
+    pub fn sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+}
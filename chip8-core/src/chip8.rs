@@ -68,8 +68,9 @@ where
             audio.map(|mut audio| {
                 let status = status.clone();
                 let sound_timer = self.cpu.state().sound_timer_ptr();
+                let audio_pattern = self.cpu.state().audio_pattern_ptr();
 
-                tokio::spawn(async move { audio.run(status, sound_timer) })
+                tokio::spawn(async move { audio.run(status, sound_timer, audio_pattern) })
             })
         };
 
@@ -1,17 +1,33 @@
 pub const NUM_REGISTERS: usize = 16;
 pub const MEMORY_SIZE: usize = 4096;
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
 pub const STACK_DEPTH: usize = 16;
 pub const OPCODE_SIZE: u16 = 2;
 
 pub const FLAG_REGISTER: usize = 0xF;
 
 pub const FONTSET_START_ADDRESS: u16 = 0x0;
+pub const BIG_FONTSET_START_ADDRESS: u16 = 0x50;
 pub const PROGRAM_START_ADDRESS: u16 = 0x200;
 
 pub const NUM_KEYS: usize = 16;
 
+/// Number of RPL user flags saved by FX75. SUPER-CHIP ROMs only use the first 8.
+pub const NUM_RPL_FLAGS: usize = 16;
+
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
+pub const HIRES_DISPLAY_WIDTH: usize = 128;
+pub const HIRES_DISPLAY_HEIGHT: usize = 64;
+/// Number of XO-CHIP bitplanes, selected by the 4-bit mask of FN01.
+pub const NUM_PLANES: usize = 4;
+/// Number of pixel colors, one per combination of planes
+pub const NUM_COLORS: usize = 1 << NUM_PLANES;
+
+/// Size of the XO-CHIP audio pattern, 128 one-bit samples
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// XO-CHIP pitch that plays the pattern at 4000 samples per second
+pub const DEFAULT_PITCH: u8 = 64;
 
 pub const FONT_SIZE: usize = 5;
 const NUM_FONTS: usize = 16;
@@ -113,6 +129,185 @@ pub const FONTSET: [u8; NUM_FONTS * FONT_SIZE] = [
     0b10000000, // █
 ];
 
+pub const BIG_FONT_SIZE: usize = 10;
+pub const BIG_FONTSET: [u8; NUM_FONTS * BIG_FONT_SIZE] = [
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b00011000, //    ██
+    0b01111000, //  ████
+    0b01111000, //  ████
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b00000011, //       ██
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b00000110, //      ██
+    0b00001100, //     ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    0b00011000, //    ██
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b00000011, //       ██
+    0b00000011, //       ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b01111110, //  ██████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    //
+    0b11111100, // ██████
+    0b11111100, // ██████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111100, // ██████
+    0b11111100, // ██████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111100, // ██████
+    0b11111100, // ██████
+    //
+    0b00111100, //   ████
+    0b11111111, // ████████
+    0b11000011, // ██    ██
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11000011, // ██    ██
+    0b11111111, // ████████
+    0b00111100, //   ████
+    //
+    0b11111100, // ██████
+    0b11111110, // ███████
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11000011, // ██    ██
+    0b11111110, // ███████
+    0b11111100, // ██████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    //
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11111111, // ████████
+    0b11111111, // ████████
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11000000, // ██
+    0b11000000, // ██
+];
+
 pub const NUM_OPCODES: usize = 34;
 
 pub const TICKS_PER_TIMER: u64 = 8;
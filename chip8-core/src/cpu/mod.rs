@@ -8,14 +8,17 @@ pub use simple::SimpleCpu;
 
 use crate::{
     constants::{
-        DISPLAY_HEIGHT, DISPLAY_WIDTH, FONTSET_START_ADDRESS, FONT_SIZE, NUM_KEYS, TICKS_PER_TIMER,
+        AUDIO_PATTERN_SIZE, BIG_FONTSET_START_ADDRESS, BIG_FONT_SIZE, FONTSET_START_ADDRESS,
+        FONT_SIZE, NUM_KEYS, NUM_PLANES, OPCODE_SIZE, TICKS_PER_TIMER,
     },
     error::Chip8Error,
     input::{InputEvent, InputQueue},
     instruction::Instruction,
-    rwlock::{CheckedRead, CheckedWrite},
+    quirks::Quirks,
+    rwlock::CheckedWrite,
     state::{Address, State, Word},
     util::run_loop,
+    variant::Variant,
 };
 
 pub trait Cpu {
@@ -27,12 +30,44 @@ pub trait Cpu {
 
     fn random(&mut self) -> Word;
 
+    fn quirks(&self) -> Quirks {
+        Quirks::default()
+    }
+
+    fn variant(&self) -> Variant {
+        Variant::Chip8
+    }
+
+    /// Skips the next instruction, which is twice as long for XO-CHIP's F000 NNNN.
+    fn skip_instruction(&mut self) -> Result<(), Chip8Error> {
+        if self.variant() >= Variant::XoChip {
+            let pc = self.state().program_counter();
+            let hi = self.state().memory(pc)?;
+            let lo = self.state().memory(pc.wrapping_add(1))?;
+            if u16::from_be_bytes([hi, lo]) == 0xF000 {
+                self.state().increment_program_counter();
+            }
+        }
+        self.state().increment_program_counter();
+        Ok(())
+    }
+
+    /// Whether DXYN has to wait for the start of the next frame, in which case it stalls.
+    fn wait_for_display(&mut self) -> Result<bool, Chip8Error> {
+        if !self.quirks().display_wait || self.state().clk()? % TICKS_PER_TIMER == 0 {
+            return Ok(false);
+        }
+        let pc = self.state().program_counter();
+        self.state().set_program_counter(pc - OPCODE_SIZE);
+        Ok(true)
+    }
+
     // Instructions
     fn op_clear_display(&mut self) -> Result<(), Chip8Error> {
         self.state().clear_framebuffer()
     }
 
-    fn op_return(&mut self) {
+    fn op_return(&mut self) -> Result<(), Chip8Error> {
         self.state().pop_stack()
     }
 
@@ -40,30 +75,33 @@ pub trait Cpu {
         self.state().set_program_counter(nnn);
     }
 
-    fn op_call(&mut self, nnn: Address) {
-        self.state().push_stack(nnn);
+    fn op_call(&mut self, nnn: Address) -> Result<(), Chip8Error> {
+        self.state().push_stack(nnn)
     }
 
-    fn op_skip_equal(&mut self, x: Word, nn: Word) {
+    fn op_skip_equal(&mut self, x: Word, nn: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         if vx == nn {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
-    fn op_skip_not_equal(&mut self, x: Word, nn: Word) {
+    fn op_skip_not_equal(&mut self, x: Word, nn: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         if vx != nn {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
-    fn op_skip_equal_xy(&mut self, x: Word, y: Word) {
+    fn op_skip_equal_xy(&mut self, x: Word, y: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         let vy = self.state().register(y);
         if vx == vy {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
     fn op_load(&mut self, x: Word, nn: Word) {
@@ -86,6 +124,9 @@ pub trait Cpu {
         let vy = self.state().register(y);
         let val = vx | vy;
         self.state().set_register(x, val);
+        if self.quirks().vf_reset {
+            self.state().set_flag_register(false);
+        }
     }
 
     fn op_and(&mut self, x: Word, y: Word) {
@@ -93,6 +134,9 @@ pub trait Cpu {
         let vy = self.state().register(y);
         let val = vx & vy;
         self.state().set_register(x, val);
+        if self.quirks().vf_reset {
+            self.state().set_flag_register(false);
+        }
     }
 
     fn op_xor(&mut self, x: Word, y: Word) {
@@ -100,6 +144,9 @@ pub trait Cpu {
         let vy = self.state().register(y);
         let val = vx ^ vy;
         self.state().set_register(x, val);
+        if self.quirks().vf_reset {
+            self.state().set_flag_register(false);
+        }
     }
 
     fn op_add_xy(&mut self, x: Word, y: Word) {
@@ -120,8 +167,9 @@ pub trait Cpu {
         self.state().set_flag_register(!borrow);
     }
 
-    fn op_shift_right(&mut self, x: Word) {
-        let vx = self.state().register(x);
+    fn op_shift_right(&mut self, x: Word, y: Word) {
+        let src = if self.quirks().shift_vy { y } else { x };
+        let vx = self.state().register(src);
         let flag = (vx & 1) != 0;
         let val = vx >> 1;
 
@@ -138,8 +186,9 @@ pub trait Cpu {
         self.state().set_flag_register(!borrow);
     }
 
-    fn op_shift_left(&mut self, x: Word) {
-        let vx = self.state().register(x);
+    fn op_shift_left(&mut self, x: Word, y: Word) {
+        let src = if self.quirks().shift_vy { y } else { x };
+        let vx = self.state().register(src);
         let flag = ((vx >> 7) & 1) != 0;
         let val = vx << 1;
 
@@ -147,12 +196,13 @@ pub trait Cpu {
         self.state().set_flag_register(flag);
     }
 
-    fn op_skip_not_equal_xy(&mut self, x: Word, y: Word) {
+    fn op_skip_not_equal_xy(&mut self, x: Word, y: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         let vy = self.state().register(y);
         if vx != vy {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
     fn op_load_i(&mut self, nnn: Address) {
@@ -160,7 +210,12 @@ pub trait Cpu {
     }
 
     fn op_jump_v0(&mut self, nnn: Address) {
-        let v0 = self.state().register(0);
+        let x = if self.quirks().jump_vx {
+            (nnn >> 8) as Word
+        } else {
+            0
+        };
+        let v0 = self.state().register(x);
         let offset = (v0 as u16) + nnn;
         self.state().set_program_counter(offset);
     }
@@ -172,23 +227,49 @@ pub trait Cpu {
     }
 
     fn op_draw(&mut self, x: Word, y: Word, n: Word) -> Result<(), Chip8Error> {
+        if self.wait_for_display()? {
+            return Ok(());
+        }
+
         let vx = self.state().register(x);
         let vy = self.state().register(y);
         let vi = self.state().index_register();
 
-        let x0 = vx as usize % DISPLAY_WIDTH;
-        let y0 = vy as usize % DISPLAY_HEIGHT;
+        let (width, height) = self.state().display_size();
+        let x0 = vx as usize % width;
+        let y0 = vy as usize % height;
+        // SUPER-CHIP draws 16x16 sprites for DXY0, stored as two bytes per row
+        let (num_rows, num_cols) = if n == 0 && self.variant() >= Variant::SuperChip {
+            (16, 16)
+        } else {
+            (n as usize, 8)
+        };
+        let bytes_per_row = num_cols / 8;
+        let clip = self.quirks().clip;
         let mut flipped = false;
-        for ys in 0..n {
-            let y = (y0 + ys as usize) % DISPLAY_HEIGHT;
-            let pixels = self.state().memory(vi + ys as u16)?;
-            for xs in 0..8 {
-                let x = (x0 + xs) % DISPLAY_WIDTH;
-                let pixel = (pixels >> (7 - xs)) & 1 == 1;
-                let fb = self.state().frame_buffer(y, x)?;
-                flipped |= pixel & fb;
-                if pixel {
-                    self.state().set_frame_buffer(y, x, !fb)?;
+        // Each selected XO-CHIP plane is drawn with the next sprite in memory
+        let planes = self.state().planes();
+        let selected_planes = (0..NUM_PLANES).filter(|plane| (planes >> plane) & 1 == 1);
+        for (i, plane) in selected_planes.enumerate() {
+            let sprite = vi.wrapping_add((i * num_rows * bytes_per_row) as u16);
+            for ys in 0..num_rows {
+                let y = (y0 + ys) % height;
+                let mut pixels = 0u16;
+                for j in 0..bytes_per_row {
+                    let addr = sprite.wrapping_add((ys * bytes_per_row + j) as u16);
+                    pixels = (pixels << 8) | self.state().memory(addr)? as u16;
+                }
+                for xs in 0..num_cols {
+                    let x = (x0 + xs) % width;
+                    if clip && (x0 + xs >= width || y0 + ys >= height) {
+                        continue;
+                    }
+                    let pixel = (pixels >> (num_cols - 1 - xs)) & 1 == 1;
+                    let fb = self.state().frame_buffer(plane, y, x)?;
+                    flipped |= pixel & fb;
+                    if pixel {
+                        self.state().set_frame_buffer(plane, y, x, !fb)?;
+                    }
                 }
             }
         }
@@ -196,18 +277,20 @@ pub trait Cpu {
         Ok(())
     }
 
-    fn op_skip_key_pressed(&mut self, x: Word) {
+    fn op_skip_key_pressed(&mut self, x: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         if self.state().key(vx) {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
-    fn op_skip_key_not_pressed(&mut self, x: Word) {
+    fn op_skip_key_not_pressed(&mut self, x: Word) -> Result<(), Chip8Error> {
         let vx = self.state().register(x);
         if !self.state().key(vx) {
-            self.state().increment_program_counter();
+            self.skip_instruction()?;
         }
+        Ok(())
     }
 
     fn op_load_delay(&mut self, x: Word) {
@@ -215,28 +298,26 @@ pub trait Cpu {
         self.state().set_register(x, val);
     }
 
-    fn op_wait_key_press(
-        &mut self,
-        status: Arc<RwLock<Result<(), Chip8Error>>>,
-        input_queue: Arc<RwLock<VecDeque<(u64, InputEvent)>>>,
-        x: Word,
-    ) -> Result<(), Chip8Error> {
-        let clk = self.state().clk()?;
-
-        let mut pressed = false;
-        for i in 0..NUM_KEYS {
-            if self.state().key(i as u8) {
-                pressed = true;
+    /// Waits for a key to be pressed and released as on the COSMAC VIP. Keys already held when
+    /// the wait starts are ignored. The CPU stalls on this instruction until the release, while
+    /// the timers keep running.
+    fn op_wait_key_press(&mut self, x: Word) {
+        match self.state().wait_key() {
+            Some(key) => {
+                if !self.state().key(key) {
+                    self.state().set_wait_key(None);
+                    self.state().set_register(x, key);
+                    return;
+                }
             }
-        }
-        while status.checked_read()?.is_ok() && !pressed {
-            if let Some(event) = (*input_queue.checked_write()?).dequeue(clk) {
-                self.state().set_key(event.key, event.kind);
-                self.state().set_register(x, event.key as u8);
-                pressed = true;
+            None => {
+                if let Some(key) = (0..NUM_KEYS as Word).find(|&i| self.state().key_pressed(i)) {
+                    self.state().set_wait_key(Some(key));
+                }
             }
         }
-        Ok(())
+        let pc = self.state().program_counter();
+        self.state().set_program_counter(pc - OPCODE_SIZE);
     }
 
     fn op_set_delay(&mut self, x: Word) {
@@ -277,6 +358,10 @@ pub trait Cpu {
             let vj = self.state().register(j);
             self.state().set_memory(vi + j as u16, vj)?;
         }
+        if self.quirks().memory_increment {
+            self.state()
+                .set_index_register(vi.wrapping_add(x as u16 + 1));
+        }
         Ok(())
     }
 
@@ -286,9 +371,107 @@ pub trait Cpu {
             let val = self.state().memory(vi + j as u16)?;
             self.state().set_register(j, val);
         }
+        if self.quirks().memory_increment {
+            self.state()
+                .set_index_register(vi.wrapping_add(x as u16 + 1));
+        }
         Ok(())
     }
 
+    fn op_scroll_down(&mut self, n: Word) -> Result<(), Chip8Error> {
+        self.state().scroll_framebuffer(0, n as isize)
+    }
+
+    fn op_scroll_right(&mut self) -> Result<(), Chip8Error> {
+        self.state().scroll_framebuffer(4, 0)
+    }
+
+    fn op_scroll_left(&mut self) -> Result<(), Chip8Error> {
+        self.state().scroll_framebuffer(-4, 0)
+    }
+
+    fn op_exit(&mut self) -> Result<(), Chip8Error> {
+        Err(Chip8Error::Terminated)
+    }
+
+    fn op_set_hires(&mut self, hires: bool) -> Result<(), Chip8Error> {
+        self.state().set_hires(hires)
+    }
+
+    fn op_load_big_font(&mut self, x: Word) {
+        let vx = self.state().register(x);
+        let addr = BIG_FONTSET_START_ADDRESS + (BIG_FONT_SIZE as u16) * (vx as u16);
+        self.state().set_index_register(addr);
+    }
+
+    fn op_store_flags(&mut self, x: Word) {
+        for j in 0..=x {
+            let vj = self.state().register(j);
+            self.state().set_rpl_flag(j, vj);
+        }
+    }
+
+    fn op_load_flags(&mut self, x: Word) {
+        for j in 0..=x {
+            let val = self.state().rpl_flag(j);
+            self.state().set_register(j, val);
+        }
+    }
+
+    fn op_scroll_up(&mut self, n: Word) -> Result<(), Chip8Error> {
+        self.state().scroll_framebuffer(0, -(n as isize))
+    }
+
+    /// Stores registers `x` to `y` from I without changing it, in reverse order if `x > y`.
+    fn op_store_range(&mut self, x: Word, y: Word) -> Result<(), Chip8Error> {
+        let vi = self.state().index_register();
+        for (offset, j) in register_range(x, y).enumerate() {
+            let vj = self.state().register(j);
+            self.state()
+                .set_memory(vi.wrapping_add(offset as u16), vj)?;
+        }
+        Ok(())
+    }
+
+    fn op_load_range(&mut self, x: Word, y: Word) -> Result<(), Chip8Error> {
+        let vi = self.state().index_register();
+        for (offset, j) in register_range(x, y).enumerate() {
+            let val = self.state().memory(vi.wrapping_add(offset as u16))?;
+            self.state().set_register(j, val);
+        }
+        Ok(())
+    }
+
+    /// Loads the address in the two bytes after the opcode, and skips them.
+    fn op_load_long_i(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.state().program_counter();
+        let hi = self.state().memory(pc)?;
+        let lo = self.state().memory(pc.wrapping_add(1))?;
+
+        self.state().increment_program_counter();
+        self.state()
+            .set_index_register(u16::from_be_bytes([hi, lo]));
+        Ok(())
+    }
+
+    fn op_select_planes(&mut self, n: Word) {
+        self.state().set_planes(n);
+    }
+
+    fn op_load_audio_pattern(&mut self) -> Result<(), Chip8Error> {
+        let vi = self.state().index_register();
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        for (j, sample) in pattern.iter_mut().enumerate() {
+            *sample = self.state().memory(vi.wrapping_add(j as u16))?;
+        }
+        self.state().set_audio_pattern(pattern)
+    }
+
+    fn op_set_pitch(&mut self, x: Word) -> Result<(), Chip8Error> {
+        let vx = self.state().register(x);
+        self.state().set_pitch(vx)
+    }
+
     // Fetch - Decode - Execute
     fn fetch(&mut self) -> Result<u16, Chip8Error> {
         let pc = self.state().program_counter();
@@ -307,12 +490,29 @@ pub trait Cpu {
         let nn = (opcode & 0x00FF) as u8;
         let nnn = opcode & 0x0FFF;
 
+        let schip = self.variant() >= Variant::SuperChip;
+        let xo = self.variant() >= Variant::XoChip;
+
         match opcode & 0xF000 {
             0x0000 => match opcode & 0xF0FF {
                 // 0x00E0
                 0x00E0 => Ok(Instruction::ClearDisplay),
                 // 0x00EE
                 0x00EE => Ok(Instruction::Return),
+                // 0x00FB
+                0x00FB if schip => Ok(Instruction::ScrollRight),
+                // 0x00FC
+                0x00FC if schip => Ok(Instruction::ScrollLeft),
+                // 0x00FD
+                0x00FD if schip => Ok(Instruction::Exit),
+                // 0x00FE
+                0x00FE if schip => Ok(Instruction::LowRes),
+                // 0x00FF
+                0x00FF if schip => Ok(Instruction::HighRes),
+                // 0x00CN
+                _ if schip && opcode & 0xFFF0 == 0x00C0 => Ok(Instruction::ScrollDown(n)),
+                // 0x00DN
+                _ if xo && opcode & 0xFFF0 == 0x00D0 => Ok(Instruction::ScrollUp(n)),
                 _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
             },
             // 0x1NNN
@@ -326,6 +526,10 @@ pub trait Cpu {
             // 0x5XY0
             0x5000 => match opcode & 0xF00F {
                 0x5000 => Ok(Instruction::SkipEqualXY(x, y)),
+                // 0x5XY2
+                0x5002 if xo => Ok(Instruction::StoreRange(x, y)),
+                // 0x5XY3
+                0x5003 if xo => Ok(Instruction::LoadRange(x, y)),
                 _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
             },
             // 0x6XNN
@@ -346,11 +550,11 @@ pub trait Cpu {
                 // 0x8XY5
                 0x8005 => Ok(Instruction::SubXY(x, y)),
                 // 0x8XY6
-                0x8006 => Ok(Instruction::ShiftRight(x)),
+                0x8006 => Ok(Instruction::ShiftRight(x, y)),
                 // 0x8XY7
                 0x8007 => Ok(Instruction::SubYX(x, y)),
                 // 0x8XYE
-                0x800E => Ok(Instruction::ShiftLeft(x)),
+                0x800E => Ok(Instruction::ShiftLeft(x, y)),
                 _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
             },
             0x9000 => match opcode & 0xF00F {
@@ -392,39 +596,48 @@ pub trait Cpu {
                 0xF055 => Ok(Instruction::StoreRegisters(x)),
                 // 0xFX65
                 0xF065 => Ok(Instruction::LoadMemory(x)),
+                // 0xFX30
+                0xF030 if schip => Ok(Instruction::LoadBigFont(x)),
+                // 0xFX75
+                0xF075 if schip => Ok(Instruction::StoreFlags(x)),
+                // 0xFX85
+                0xF085 if schip => Ok(Instruction::LoadFlags(x)),
+                // 0xF000 0xNNNN
+                _ if xo && opcode == 0xF000 => Ok(Instruction::LoadLongI),
+                // 0xFN01
+                0xF001 if xo => Ok(Instruction::SelectPlanes(x)),
+                // 0xF002
+                _ if xo && opcode == 0xF002 => Ok(Instruction::LoadAudioPattern),
+                // 0xFX3A
+                0xF03A if xo => Ok(Instruction::SetPitch(x)),
                 _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
             },
             _ => Err(Chip8Error::UnimplementedOpcode(opcode)),
         }
     }
 
-    fn execute(
-        &mut self,
-        instruction: Instruction,
-        status: Arc<RwLock<Result<(), Chip8Error>>>,
-        input_queue: Arc<RwLock<VecDeque<(u64, InputEvent)>>>,
-    ) -> Result<(), Chip8Error> {
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
         match instruction {
             Instruction::ClearDisplay => {
                 self.op_clear_display()?;
             }
             Instruction::Return => {
-                self.op_return();
+                self.op_return()?;
             }
             Instruction::Jump(nnn) => {
                 self.op_jump(nnn);
             }
             Instruction::Call(nnn) => {
-                self.op_call(nnn);
+                self.op_call(nnn)?;
             }
             Instruction::SkipEqual(x, nn) => {
-                self.op_skip_equal(x, nn);
+                self.op_skip_equal(x, nn)?;
             }
             Instruction::SkipNotEqual(x, nn) => {
-                self.op_skip_not_equal(x, nn);
+                self.op_skip_not_equal(x, nn)?;
             }
             Instruction::SkipEqualXY(x, y) => {
-                self.op_skip_equal_xy(x, y);
+                self.op_skip_equal_xy(x, y)?;
             }
             Instruction::Load(x, nn) => {
                 self.op_load(x, nn);
@@ -450,17 +663,17 @@ pub trait Cpu {
             Instruction::SubXY(x, y) => {
                 self.op_sub_xy(x, y);
             }
-            Instruction::ShiftRight(x) => {
-                self.op_shift_right(x);
+            Instruction::ShiftRight(x, y) => {
+                self.op_shift_right(x, y);
             }
             Instruction::SubYX(x, y) => {
                 self.op_sub_yx(x, y);
             }
-            Instruction::ShiftLeft(x) => {
-                self.op_shift_left(x);
+            Instruction::ShiftLeft(x, y) => {
+                self.op_shift_left(x, y);
             }
             Instruction::SkipNotEqualXY(x, y) => {
-                self.op_skip_not_equal_xy(x, y);
+                self.op_skip_not_equal_xy(x, y)?;
             }
             Instruction::LoadI(nnn) => {
                 self.op_load_i(nnn);
@@ -475,16 +688,16 @@ pub trait Cpu {
                 self.op_draw(x, y, n)?;
             }
             Instruction::SkipKeyPressed(x) => {
-                self.op_skip_key_pressed(x);
+                self.op_skip_key_pressed(x)?;
             }
             Instruction::SkipKeyNotPressed(x) => {
-                self.op_skip_key_not_pressed(x);
+                self.op_skip_key_not_pressed(x)?;
             }
             Instruction::LoadDelay(x) => {
                 self.op_load_delay(x);
             }
             Instruction::WaitKeyPress(x) => {
-                self.op_wait_key_press(status, input_queue, x)?;
+                self.op_wait_key_press(x);
             }
             Instruction::SetDelay(x) => {
                 self.op_set_delay(x);
@@ -507,20 +720,64 @@ pub trait Cpu {
             Instruction::LoadMemory(x) => {
                 self.op_load_memory(x)?;
             }
+            Instruction::ScrollDown(n) => {
+                self.op_scroll_down(n)?;
+            }
+            Instruction::ScrollRight => {
+                self.op_scroll_right()?;
+            }
+            Instruction::ScrollLeft => {
+                self.op_scroll_left()?;
+            }
+            Instruction::Exit => {
+                self.op_exit()?;
+            }
+            Instruction::LowRes => {
+                self.op_set_hires(false)?;
+            }
+            Instruction::HighRes => {
+                self.op_set_hires(true)?;
+            }
+            Instruction::LoadBigFont(x) => {
+                self.op_load_big_font(x);
+            }
+            Instruction::StoreFlags(x) => {
+                self.op_store_flags(x);
+            }
+            Instruction::LoadFlags(x) => {
+                self.op_load_flags(x);
+            }
+            Instruction::ScrollUp(n) => {
+                self.op_scroll_up(n)?;
+            }
+            Instruction::StoreRange(x, y) => {
+                self.op_store_range(x, y)?;
+            }
+            Instruction::LoadRange(x, y) => {
+                self.op_load_range(x, y)?;
+            }
+            Instruction::LoadLongI => {
+                self.op_load_long_i()?;
+            }
+            Instruction::SelectPlanes(n) => {
+                self.op_select_planes(n);
+            }
+            Instruction::LoadAudioPattern => {
+                self.op_load_audio_pattern()?;
+            }
+            Instruction::SetPitch(x) => {
+                self.op_set_pitch(x)?;
+            }
         }
 
         Ok(())
     }
 
     // Cycle
-    fn tick(
-        &mut self,
-        status: Arc<RwLock<Result<(), Chip8Error>>>,
-        input_queue: Arc<RwLock<VecDeque<(u64, InputEvent)>>>,
-    ) -> Result<(), Chip8Error> {
+    fn tick(&mut self) -> Result<(), Chip8Error> {
         let op = self.fetch()?;
         let instruction = self.decode(op)?;
-        self.execute(instruction, status, input_queue)
+        self.execute(instruction)
     }
 
     fn tick_timers(&mut self) -> Result<(), Chip8Error> {
@@ -533,6 +790,20 @@ pub trait Cpu {
         Ok(())
     }
 
+    /// Runs one clock cycle, applying the inputs from `input_queue` that are due first.
+    fn step(&mut self, input_queue: &mut impl InputQueue) -> Result<(), Chip8Error> {
+        let clk = self.state().clk()?;
+        while let Some(event) = input_queue.dequeue(clk) {
+            self.state().set_key(event.key, event.kind);
+        }
+        self.tick()?;
+        if clk % TICKS_PER_TIMER == 0 {
+            self.tick_timers()?;
+        }
+
+        self.state().increment_clk()
+    }
+
     async fn run(
         &mut self,
         num_cycles: Option<u64>,
@@ -547,17 +818,16 @@ pub trait Cpu {
                 }
             }
 
-            while let Some(event) = (*input_queue.checked_write()?).dequeue(clk) {
-                self.state().set_key(event.key, event.kind);
-            }
-            // TODO: How do I remove this clone?
-            self.tick(status.clone(), input_queue.clone())?;
-            if clk % TICKS_PER_TIMER == 0 {
-                self.tick_timers()?;
-            }
-
-            self.state().increment_clk()?;
-            Ok(())
+            self.step(&mut *input_queue.checked_write()?)
         })
     }
 }
+
+/// Register indices from `x` to `y` inclusive, counting down if `x > y`.
+fn register_range(x: Word, y: Word) -> Box<dyn Iterator<Item = Word>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
+}
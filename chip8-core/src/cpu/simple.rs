@@ -1,13 +1,21 @@
 use rand::Rng;
 
 use super::Cpu;
-use crate::state::{SimpleState, Word};
+use crate::{
+    quirks::Quirks,
+    rng::Lfsr,
+    state::{SimpleState, Word},
+    variant::Variant,
+};
 
-pub struct SimpleCpu<R: Rng> {
+/// Reference CPU. CXNN only matches the proven execution with the [`Lfsr`] generator.
+pub struct SimpleCpu<R: Rng = Lfsr> {
     // TODO: Make private
     pub state: SimpleState,
     pub clk_freq: u64,
     pub rng: R,
+    pub quirks: Quirks,
+    pub variant: Variant,
 }
 
 impl<R: Rng> SimpleCpu<R> {
@@ -16,8 +24,21 @@ impl<R: Rng> SimpleCpu<R> {
             state: SimpleState::default(),
             clk_freq,
             rng,
+            quirks: Quirks::default(),
+            variant: Variant::default(),
         }
     }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self.state.memory.resize(variant.memory_size(), 0);
+        self
+    }
 }
 
 impl<R: Rng> Cpu for SimpleCpu<R> {
@@ -34,4 +55,12 @@ impl<R: Rng> Cpu for SimpleCpu<R> {
     fn frequency(&self) -> u64 {
         self.clk_freq
     }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    fn variant(&self) -> Variant {
+        self.variant
+    }
 }
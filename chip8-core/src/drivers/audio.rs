@@ -1,16 +1,31 @@
 use std::sync::{Arc, RwLock};
 
-use crate::{error::Chip8Error, rwlock::CheckedRead, util::run_loop};
+use crate::{audio::AudioPattern, error::Chip8Error, rwlock::CheckedRead, util::run_loop};
 
 pub trait AudioDriver: Send {
     fn frequency(&self) -> u64;
 
     fn beep(&mut self) -> Result<(), Chip8Error>;
 
-    fn run(&mut self, status: Arc<RwLock<Result<(), Chip8Error>>>, sound_timer: Arc<RwLock<u8>>) {
+    /// Plays the XO-CHIP audio pattern for one tick. Drivers that can't play samples beep.
+    fn play(&mut self, _pattern: &AudioPattern) -> Result<(), Chip8Error> {
+        self.beep()
+    }
+
+    fn run(
+        &mut self,
+        status: Arc<RwLock<Result<(), Chip8Error>>>,
+        sound_timer: Arc<RwLock<u8>>,
+        audio_pattern: Arc<RwLock<AudioPattern>>,
+    ) {
         run_loop(status.clone(), self.frequency(), move |_| {
             if *sound_timer.checked_read()? > 0 {
-                self.beep()?;
+                let pattern = audio_pattern.checked_read()?.clone();
+                if pattern.buffer.is_some() {
+                    self.play(&pattern)?;
+                } else {
+                    self.beep()?;
+                }
             }
             Ok(())
         });
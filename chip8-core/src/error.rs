@@ -8,6 +8,10 @@ pub enum Chip8Error {
     MemoryAccessOutOfBounds(Address),
     #[error("Unimplemented opcode: 0x{0:04X}")]
     UnimplementedOpcode(u16),
+    #[error("Stack overflow")]
+    StackOverflow,
+    #[error("Stack underflow")]
+    StackUnderflow,
     #[error("ROM size too big: {0}bytes")]
     RomTooBig(usize),
     #[error("Display Error: {0}")]
@@ -16,6 +20,8 @@ pub enum Chip8Error {
     InputError(String),
     #[error("Audio Error: {0}")]
     AudioError(String),
+    #[error("Prover Error: {0}")]
+    ProverError(String),
     #[error("Async/Await Error: {0}")]
     AsyncAwaitError(String),
     #[error("Mutex read error: {0}")]
@@ -0,0 +1,101 @@
+use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
+
+/// Frame buffer in either the low or the high resolution display mode.
+///
+/// Every pixel holds one bit per XO-CHIP plane, so it has one of `1 << NUM_PLANES` colors.
+/// CHIP-8 and SUPER-CHIP only use the first plane.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl FrameBuffer {
+    /// Blank frame buffer, 64x32 in low resolution and 128x64 in high resolution.
+    pub fn new(hires: bool) -> Self {
+        let (width, height) = if hires {
+            (HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        };
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.width == HIRES_DISPLAY_WIDTH
+    }
+
+    pub fn get(&self, plane: usize, y: usize, x: usize) -> bool {
+        (self.pixels[y * self.width + x] >> plane) & 1 == 1
+    }
+
+    pub fn set(&mut self, plane: usize, y: usize, x: usize, bit: bool) {
+        let pixel = &mut self.pixels[y * self.width + x];
+        *pixel = (*pixel & !(1 << plane)) | ((bit as u8) << plane);
+    }
+
+    /// Color of the pixel, with bit `i` set if it is on in plane `i`.
+    pub fn color(&self, y: usize, x: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Pixels of `plane` in row-major order.
+    pub fn plane(&self, plane: usize) -> impl Iterator<Item = bool> + '_ {
+        self.pixels
+            .iter()
+            .map(move |pixel| (pixel >> plane) & 1 == 1)
+    }
+
+    /// Pixel colors row by row.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks_exact(self.width)
+    }
+
+    /// Clears the planes selected by the `planes` bitmask.
+    pub fn clear(&mut self, planes: u8) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !planes;
+        }
+    }
+
+    /// Moves the picture in the planes selected by the `planes` bitmask by `dx` pixels to the
+    /// right and `dy` pixels down. Pixels scrolled off the screen are lost and the uncovered ones
+    /// are cleared.
+    pub fn scroll(&mut self, planes: u8, dx: isize, dy: isize) {
+        let prev = self.pixels.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let src_y = y as isize - dy;
+                let src_x = x as isize - dx;
+                let is_inside = (0..self.height as isize).contains(&src_y)
+                    && (0..self.width as isize).contains(&src_x);
+                let src = if is_inside {
+                    prev[src_y as usize * self.width + src_x as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixels[y * self.width + x];
+                *pixel = (*pixel & !planes) | (src & planes);
+            }
+        }
+    }
+}
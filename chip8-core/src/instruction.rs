@@ -21,9 +21,9 @@ pub enum Instruction {
     Xor(RegisterIndex, RegisterIndex),
     AddXY(RegisterIndex, RegisterIndex),
     SubXY(RegisterIndex, RegisterIndex),
-    ShiftRight(RegisterIndex),
+    ShiftRight(RegisterIndex, RegisterIndex),
     SubYX(RegisterIndex, RegisterIndex),
-    ShiftLeft(RegisterIndex),
+    ShiftLeft(RegisterIndex, RegisterIndex),
 
     SkipNotEqualXY(RegisterIndex, RegisterIndex),
     LoadI(Address),
@@ -43,4 +43,24 @@ pub enum Instruction {
     StoreBCD(RegisterIndex),
     StoreRegisters(RegisterIndex),
     LoadMemory(RegisterIndex),
+
+    // SUPER-CHIP
+    ScrollDown(Nibble),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
+    LoadBigFont(RegisterIndex),
+    StoreFlags(RegisterIndex),
+    LoadFlags(RegisterIndex),
+
+    // XO-CHIP
+    ScrollUp(Nibble),
+    StoreRange(RegisterIndex, RegisterIndex),
+    LoadRange(RegisterIndex, RegisterIndex),
+    LoadLongI,
+    SelectPlanes(Nibble),
+    LoadAudioPattern,
+    SetPitch(RegisterIndex),
 }
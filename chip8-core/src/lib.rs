@@ -1,13 +1,18 @@
+pub mod audio;
 mod chip8;
 pub mod constants;
 pub mod cpu;
 pub mod drivers;
 pub mod error;
+pub mod frame_buffer;
 pub mod input;
 pub mod instruction;
 pub mod keypad;
+pub mod quirks;
+pub mod rng;
 pub mod rwlock;
 pub mod state;
 pub mod util;
+pub mod variant;
 
 pub use chip8::*;
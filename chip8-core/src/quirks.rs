@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Behaviors that differ between CHIP-8 platforms. The default is none of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift vy into vx instead of shifting vx in place.
+    pub shift_vy: bool,
+    /// FX55 and FX65 leave I past the last register transferred.
+    pub memory_increment: bool,
+    /// BXNN jumps to XNN plus vx instead of NNN plus v0.
+    pub jump_vx: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF.
+    pub vf_reset: bool,
+    /// DXYN waits for the start of a frame, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter.
+    pub const VIP: Self = Self {
+        shift_vy: true,
+        memory_increment: true,
+        jump_vx: false,
+        clip: true,
+        vf_reset: true,
+        display_wait: true,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP 48.
+    pub const SCHIP: Self = Self {
+        shift_vy: false,
+        memory_increment: false,
+        jump_vx: true,
+        clip: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    /// XO-CHIP as implemented by Octo.
+    pub const XO_CHIP: Self = Self {
+        shift_vy: true,
+        memory_increment: true,
+        jump_vx: false,
+        clip: false,
+        vf_reset: false,
+        display_wait: false,
+    };
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses the name of a preset.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Quirks::default()),
+            "vip" => Ok(Quirks::VIP),
+            "schip" => Ok(Quirks::SCHIP),
+            "xochip" => Ok(Quirks::XO_CHIP),
+            _ => Err(format!("Unknown quirks preset: {s}")),
+        }
+    }
+}
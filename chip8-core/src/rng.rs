@@ -0,0 +1,62 @@
+use rand::{Error, RngCore};
+
+pub const LFSR_BITS: usize = 16;
+/// Bit positions XOR-ed into the feedback bit, for the primitive polynomial
+/// x^16 + x^14 + x^13 + x^11 + 1.
+pub const LFSR_TAPS: [usize; 4] = [0, 2, 3, 5];
+/// Number of shifts per generated byte
+pub const LFSR_SHIFTS_PER_BYTE: usize = 8;
+
+/// 16-bit Fibonacci LFSR used by CXNN.
+///
+/// Each byte is the low byte of the state after 8 shifts, which is simple enough to be
+/// reproduced in-circuit. A zero seed only ever generates zeros.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lfsr {
+    state: u16,
+}
+
+impl Lfsr {
+    pub fn new(seed: u16) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn state(&self) -> u16 {
+        self.state
+    }
+
+    /// State after generating one byte from `state`.
+    pub fn step(state: u16) -> u16 {
+        (0..LFSR_SHIFTS_PER_BYTE).fold(state, |state, _| {
+            let bit = LFSR_TAPS.iter().fold(0, |bit, &tap| bit ^ (state >> tap)) & 1;
+            (state >> 1) | (bit << (LFSR_BITS - 1))
+        })
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        self.state = Self::step(self.state);
+        self.state as u8
+    }
+}
+
+// Every `u32` holds a single byte so that `Rng::gen::<u8>` draws the same bytes as `next_byte`
+impl RngCore for Lfsr {
+    fn next_u32(&mut self) -> u32 {
+        self.next_byte() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let lo = self.next_u32() as u64;
+        let hi = self.next_u32() as u64;
+        (hi << 32) | lo
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.fill_with(|| self.next_byte());
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
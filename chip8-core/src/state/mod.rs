@@ -1,8 +1,13 @@
 use std::sync::{Arc, RwLock};
 
 use crate::{
-    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    audio::AudioPattern,
+    constants::{
+        AUDIO_PATTERN_SIZE, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT,
+        HIRES_DISPLAY_WIDTH,
+    },
     error::Chip8Error,
+    frame_buffer::FrameBuffer,
     input::InputKind,
     keypad::Key,
 };
@@ -24,9 +29,33 @@ pub trait State: Default {
     fn register(&self, index: Word) -> Word;
     fn index_register(&self) -> Address;
     fn key(&self, index: Word) -> bool;
-    fn frame_buffer(&mut self, y: usize, x: usize) -> Result<bool, Chip8Error>;
+    /// Whether the key went down during the current cycle.
+    fn key_pressed(&self, index: Word) -> bool;
+    /// Key that FX0A saw pressed and waits to be released.
+    fn wait_key(&self) -> Option<Word>;
+    fn frame_buffer(&mut self, plane: usize, y: usize, x: usize) -> Result<bool, Chip8Error>;
+    /// Whether the display is in the SUPER-CHIP 128x64 mode.
+    fn is_hires(&self) -> bool;
+    fn rpl_flag(&self, index: Word) -> Word;
+    /// Bitmask of the XO-CHIP planes that are drawn, cleared and scrolled.
+    fn planes(&self) -> Word;
 
-    fn set_frame_buffer(&mut self, y: usize, x: usize, bit: bool) -> Result<(), Chip8Error>;
+    /// Width and height of the display in the current mode.
+    fn display_size(&self) -> (usize, usize) {
+        if self.is_hires() {
+            (HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        }
+    }
+
+    fn set_frame_buffer(
+        &mut self,
+        plane: usize,
+        y: usize,
+        x: usize,
+        bit: bool,
+    ) -> Result<(), Chip8Error>;
     fn set_program_counter(&mut self, pc: Address);
     fn set_delay_timer(&mut self, value: Word);
     fn set_sound_timer(&mut self, value: Word) -> Result<(), Chip8Error>;
@@ -35,10 +64,20 @@ pub trait State: Default {
     fn set_flag_register(&mut self, flag: bool);
     fn set_memory(&mut self, addr: Address, value: Word) -> Result<(), Chip8Error>;
     fn set_key(&mut self, key: Key, kind: InputKind);
+    fn set_wait_key(&mut self, key: Option<Word>);
+    /// Switches the display mode, which clears the screen.
+    fn set_hires(&mut self, hires: bool) -> Result<(), Chip8Error>;
+    fn set_rpl_flag(&mut self, index: Word, value: Word);
+    fn set_planes(&mut self, planes: Word);
+    fn set_audio_pattern(&mut self, pattern: [Word; AUDIO_PATTERN_SIZE]) -> Result<(), Chip8Error>;
+    fn set_pitch(&mut self, pitch: Word) -> Result<(), Chip8Error>;
 
+    /// Clears the selected planes.
     fn clear_framebuffer(&mut self) -> Result<(), Chip8Error>;
-    fn push_stack(&mut self, addr: Address);
-    fn pop_stack(&mut self);
+    /// Moves the picture in the selected planes by `dx` pixels to the right and `dy` pixels down.
+    fn scroll_framebuffer(&mut self, dx: isize, dy: isize) -> Result<(), Chip8Error>;
+    fn push_stack(&mut self, addr: Address) -> Result<(), Chip8Error>;
+    fn pop_stack(&mut self) -> Result<(), Chip8Error>;
     fn increment_program_counter(&mut self);
     fn increment_clk(&mut self) -> Result<(), Chip8Error>;
     fn decrement_delay_timer(&mut self);
@@ -46,5 +85,6 @@ pub trait State: Default {
 
     fn clk_ptr(&self) -> Arc<RwLock<u64>>;
     fn sound_timer_ptr(&self) -> Arc<RwLock<Word>>;
-    fn frame_buffer_ptr(&self) -> Arc<RwLock<[[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT]>>;
+    fn frame_buffer_ptr(&self) -> Arc<RwLock<FrameBuffer>>;
+    fn audio_pattern_ptr(&self) -> Arc<RwLock<AudioPattern>>;
 }
@@ -2,11 +2,14 @@ use std::sync::{Arc, RwLock};
 
 use super::{Address, State, Word};
 use crate::{
+    audio::AudioPattern,
     constants::{
-        DISPLAY_HEIGHT, DISPLAY_WIDTH, FLAG_REGISTER, FONTSET, FONTSET_START_ADDRESS, MEMORY_SIZE,
-        NUM_KEYS, NUM_REGISTERS, OPCODE_SIZE, PROGRAM_START_ADDRESS, STACK_DEPTH,
+        AUDIO_PATTERN_SIZE, BIG_FONTSET, BIG_FONTSET_START_ADDRESS, FLAG_REGISTER, FONTSET,
+        FONTSET_START_ADDRESS, MEMORY_SIZE, NUM_KEYS, NUM_REGISTERS, NUM_RPL_FLAGS, OPCODE_SIZE,
+        PROGRAM_START_ADDRESS, STACK_DEPTH,
     },
     error::Chip8Error,
+    frame_buffer::FrameBuffer,
     input::InputKind,
     keypad::Key,
     rwlock::{CheckedRead, CheckedWrite},
@@ -18,7 +21,8 @@ pub struct SimpleState {
     // TODO: Make private
     pub clk: Arc<RwLock<u64>>,
     pub registers: [Word; NUM_REGISTERS],
-    pub memory: [Word; MEMORY_SIZE],
+    /// 4KB, or 64KB for XO-CHIP
+    pub memory: Vec<Word>,
     pub index_register: Address,
     pub program_counter: Address,
     pub stack: [Address; STACK_DEPTH],
@@ -26,15 +30,29 @@ pub struct SimpleState {
     pub delay_timer: Word,
     pub sound_timer: Arc<RwLock<Word>>,
     pub keypad: [bool; NUM_KEYS],
-    pub frame_buffer: Arc<RwLock<[[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT]>>,
+    /// Keypad at the end of the previous cycle, to tell which keys went down in this one.
+    pub prev_keypad: [bool; NUM_KEYS],
+    /// Key pressed while FX0A waits for its release.
+    pub wait_key: Option<Word>,
+    /// Whether the display is in the 128x64 mode.
+    pub hires: bool,
+    pub frame_buffer: Arc<RwLock<FrameBuffer>>,
+    /// XO-CHIP planes selected by FN01
+    pub planes: Word,
+    pub audio_pattern: Arc<RwLock<AudioPattern>>,
+    /// SUPER-CHIP RPL user flags, which outlive the program on real calculators.
+    pub rpl_flags: [Word; NUM_RPL_FLAGS],
 }
 
 impl Default for SimpleState {
     fn default() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+        let mut memory = vec![0; MEMORY_SIZE];
         let start = FONTSET_START_ADDRESS as usize;
         let end = FONTSET_START_ADDRESS as usize + FONTSET.len();
         memory[start..end].copy_from_slice(FONTSET.as_slice());
+        let start = BIG_FONTSET_START_ADDRESS as usize;
+        let end = BIG_FONTSET_START_ADDRESS as usize + BIG_FONTSET.len();
+        memory[start..end].copy_from_slice(BIG_FONTSET.as_slice());
 
         Self {
             clk: Arc::new(RwLock::new(0)),
@@ -47,7 +65,13 @@ impl Default for SimpleState {
             delay_timer: 0,
             sound_timer: Arc::new(RwLock::new(0)),
             keypad: [false; NUM_KEYS],
-            frame_buffer: Arc::new(RwLock::new([[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT])),
+            prev_keypad: [false; NUM_KEYS],
+            wait_key: None,
+            hires: false,
+            frame_buffer: Arc::new(RwLock::new(FrameBuffer::default())),
+            planes: 1,
+            audio_pattern: Arc::new(RwLock::new(AudioPattern::default())),
+            rpl_flags: [0; NUM_RPL_FLAGS],
         }
     }
 }
@@ -57,7 +81,7 @@ impl State for SimpleState {
         let start = PROGRAM_START_ADDRESS as usize;
         let end = PROGRAM_START_ADDRESS as usize + bytes.len();
 
-        if end > MEMORY_SIZE {
+        if end > self.memory.len() {
             Err(Chip8Error::RomTooBig(bytes.len()))
         } else {
             self.memory[start..end].copy_from_slice(bytes);
@@ -78,10 +102,14 @@ impl State for SimpleState {
         self.sound_timer.clone()
     }
 
-    fn frame_buffer_ptr(&self) -> Arc<RwLock<[[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT]>> {
+    fn frame_buffer_ptr(&self) -> Arc<RwLock<FrameBuffer>> {
         self.frame_buffer.clone()
     }
 
+    fn audio_pattern_ptr(&self) -> Arc<RwLock<AudioPattern>> {
+        self.audio_pattern.clone()
+    }
+
     fn program_counter(&self) -> Address {
         self.program_counter
     }
@@ -96,7 +124,7 @@ impl State for SimpleState {
     }
 
     fn memory(&mut self, addr: Address) -> Result<Word, Chip8Error> {
-        if (addr as usize) < MEMORY_SIZE {
+        if (addr as usize) < self.memory.len() {
             Ok(self.memory[addr as usize])
         } else {
             Err(Chip8Error::MemoryAccessOutOfBounds(addr))
@@ -115,13 +143,39 @@ impl State for SimpleState {
         self.keypad[index as usize]
     }
 
-    fn frame_buffer(&mut self, y: usize, x: usize) -> Result<bool, Chip8Error> {
-        let fb = ((*self.frame_buffer).checked_read()?)[y][x];
+    fn key_pressed(&self, index: Word) -> bool {
+        self.keypad[index as usize] && !self.prev_keypad[index as usize]
+    }
+
+    fn wait_key(&self) -> Option<Word> {
+        self.wait_key
+    }
+
+    fn frame_buffer(&mut self, plane: usize, y: usize, x: usize) -> Result<bool, Chip8Error> {
+        let fb = (*self.frame_buffer).checked_read()?.get(plane, y, x);
         Ok(fb)
     }
 
-    fn set_frame_buffer(&mut self, y: usize, x: usize, bit: bool) -> Result<(), Chip8Error> {
-        ((*self.frame_buffer).checked_write()?)[y][x] = bit;
+    fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    fn rpl_flag(&self, index: Word) -> Word {
+        self.rpl_flags[index as usize]
+    }
+
+    fn planes(&self) -> Word {
+        self.planes
+    }
+
+    fn set_frame_buffer(
+        &mut self,
+        plane: usize,
+        y: usize,
+        x: usize,
+        bit: bool,
+    ) -> Result<(), Chip8Error> {
+        (*self.frame_buffer).checked_write()?.set(plane, y, x, bit);
         Ok(())
     }
 
@@ -151,7 +205,7 @@ impl State for SimpleState {
     }
 
     fn set_memory(&mut self, addr: Address, value: Word) -> Result<(), Chip8Error> {
-        if (addr as usize) < MEMORY_SIZE {
+        if (addr as usize) < self.memory.len() {
             self.memory[addr as usize] = value;
             Ok(())
         } else {
@@ -163,20 +217,63 @@ impl State for SimpleState {
         self.keypad[key as usize] = kind == InputKind::Press;
     }
 
+    fn set_wait_key(&mut self, key: Option<Word>) {
+        self.wait_key = key;
+    }
+
+    fn set_hires(&mut self, hires: bool) -> Result<(), Chip8Error> {
+        *self.frame_buffer.checked_write()? = FrameBuffer::new(hires);
+        self.hires = hires;
+        Ok(())
+    }
+
+    fn set_rpl_flag(&mut self, index: Word, value: Word) {
+        self.rpl_flags[index as usize] = value;
+    }
+
+    fn set_planes(&mut self, planes: Word) {
+        self.planes = planes;
+    }
+
+    fn set_audio_pattern(&mut self, pattern: [Word; AUDIO_PATTERN_SIZE]) -> Result<(), Chip8Error> {
+        self.audio_pattern.checked_write()?.buffer = Some(pattern);
+        Ok(())
+    }
+
+    fn set_pitch(&mut self, pitch: Word) -> Result<(), Chip8Error> {
+        self.audio_pattern.checked_write()?.pitch = pitch;
+        Ok(())
+    }
+
     fn clear_framebuffer(&mut self) -> Result<(), Chip8Error> {
-        *self.frame_buffer.checked_write()? = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        self.frame_buffer.checked_write()?.clear(self.planes);
+        Ok(())
+    }
+
+    fn scroll_framebuffer(&mut self, dx: isize, dy: isize) -> Result<(), Chip8Error> {
+        self.frame_buffer
+            .checked_write()?
+            .scroll(self.planes, dx, dy);
         Ok(())
     }
 
-    fn push_stack(&mut self, addr: Address) {
+    fn push_stack(&mut self, addr: Address) -> Result<(), Chip8Error> {
+        if self.stack_pointer as usize >= STACK_DEPTH {
+            return Err(Chip8Error::StackOverflow);
+        }
         self.stack[self.stack_pointer as usize] = self.program_counter;
         self.stack_pointer += 1;
         self.program_counter = addr;
+        Ok(())
     }
 
-    fn pop_stack(&mut self) {
+    fn pop_stack(&mut self) -> Result<(), Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.stack_pointer -= 1;
         self.program_counter = self.stack[self.stack_pointer as usize];
+        Ok(())
     }
 
     fn increment_program_counter(&mut self) {
@@ -185,6 +282,7 @@ impl State for SimpleState {
 
     fn increment_clk(&mut self) -> Result<(), Chip8Error> {
         *self.clk.checked_write()? += 1;
+        self.prev_keypad = self.keypad;
         Ok(())
     }
 
@@ -0,0 +1,49 @@
+use std::{fmt, str::FromStr};
+
+use crate::constants::{MEMORY_SIZE, XO_CHIP_MEMORY_SIZE};
+
+/// Instruction set understood by the CPU. Each variant extends the previous ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1 with scrolling, a 128x64 high resolution mode, 16x16 sprites, a big font
+    /// and RPL user flags.
+    SuperChip,
+    /// XO-CHIP with a 64KB address space, four drawing planes with a 16-color palette and audio
+    /// patterns.
+    XoChip,
+}
+
+impl Variant {
+    /// Size of the address space.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Variant::Chip8 | Variant::SuperChip => MEMORY_SIZE,
+            Variant::XoChip => XO_CHIP_MEMORY_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "chip8"),
+            Variant::SuperChip => write!(f, "schip"),
+            Variant::XoChip => write!(f, "xochip"),
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" => Ok(Variant::SuperChip),
+            "xochip" => Ok(Variant::XoChip),
+            _ => Err(format!("Unknown variant: {s}")),
+        }
+    }
+}
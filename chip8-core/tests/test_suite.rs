@@ -0,0 +1,583 @@
+//! Conformance harness running ROMs headless on `SimpleCpu` and comparing the final frame buffer
+//! against golden images in `tests/golden`. A missing ROM or golden image fails the case.
+//!
+//! The flags, quirks, keypad and opcode programs are vendored below as opcode listings. The
+//! Timendus chip8-test-suite and corax89 test ROM cases need the `rom/` submodules, so they are
+//! ignored until the submodules are pinned and their goldens blessed. Run with `CHIP8_BLESS=1` to
+//! write the golden images from the current output.
+
+use std::{collections::VecDeque, env, fs, path::PathBuf};
+
+use chip8_core::{
+    constants::NUM_COLORS,
+    cpu::{Cpu, SimpleCpu},
+    error::Chip8Error,
+    frame_buffer::FrameBuffer,
+    input::{InputEvent, InputKind},
+    keypad::Key,
+    quirks::Quirks,
+    rng::Lfsr,
+    state::{Address, State, Word},
+    variant::Variant,
+};
+
+const CLK_FREQ: u64 = 500;
+const RANDOM_SEED: u16 = 0xACE1;
+
+/// The test suite skips its menus when this byte is non-zero.
+const PLATFORM_ADDRESS: Address = 0x1FF;
+
+// Palette of the golden images, indexed by pixel color
+const PALETTE: [char; NUM_COLORS] = [
+    '.', '#', '+', '@', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F',
+];
+
+/// Checks the results and VF of the 8XYN arithmetic, two hex digits per value:
+/// `01 01 30 00` / `02 01 FE 00` / `02 01 02 01` / `02 01 01`.
+const FLAGS_PROGRAM: &[u16] = &[
+    0x6C00, // 200: vc = 0
+    0x6D00, // 202: vd = 0
+    0x63FF, // 204: v3 = 0xff
+    0x6402, // 206: v4 = 0x02
+    0x8344, // 208: v3 += v4
+    0x8EF0, // 20A: ve = vf
+    0x8030, // 20C: v0 = v3, 0x01
+    0x228A, // 20E: call show
+    0x80E0, // 210: v0 = ve, carry 1
+    0x228A, // 212: call show
+    0x6310, // 214: v3 = 0x10
+    0x6420, // 216: v4 = 0x20
+    0x8344, // 218: v3 += v4
+    0x8EF0, // 21A: ve = vf
+    0x8030, // 21C: v0 = v3, 0x30
+    0x228A, // 21E: call show
+    0x80E0, // 220: v0 = ve, carry 0
+    0x228A, // 222: call show
+    0x6C00, // 224: vc = 0
+    0x7D06, // 226: vd += 6
+    0x6305, // 228: v3 = 0x05
+    0x6403, // 22A: v4 = 0x03
+    0x8345, // 22C: v3 -= v4
+    0x8EF0, // 22E: ve = vf
+    0x8030, // 230: v0 = v3, 0x02
+    0x228A, // 232: call show
+    0x80E0, // 234: v0 = ve, no borrow 1
+    0x228A, // 236: call show
+    0x6303, // 238: v3 = 0x03
+    0x6405, // 23A: v4 = 0x05
+    0x8345, // 23C: v3 -= v4
+    0x8EF0, // 23E: ve = vf
+    0x8030, // 240: v0 = v3, 0xfe
+    0x228A, // 242: call show
+    0x80E0, // 244: v0 = ve, borrow 0
+    0x228A, // 246: call show
+    0x6C00, // 248: vc = 0
+    0x7D06, // 24A: vd += 6
+    0x6303, // 24C: v3 = 0x03
+    0x6405, // 24E: v4 = 0x05
+    0x8347, // 250: v3 = v4 - v3
+    0x8EF0, // 252: ve = vf
+    0x8030, // 254: v0 = v3, 0x02
+    0x228A, // 256: call show
+    0x80E0, // 258: v0 = ve, no borrow 1
+    0x228A, // 25A: call show
+    0x6305, // 25C: v3 = 0x05
+    0x8336, // 25E: v3 >>= 1
+    0x8EF0, // 260: ve = vf
+    0x8030, // 262: v0 = v3, 0x02
+    0x228A, // 264: call show
+    0x80E0, // 266: v0 = ve, shifted out 1
+    0x228A, // 268: call show
+    0x6C00, // 26A: vc = 0
+    0x7D06, // 26C: vd += 6
+    0x6381, // 26E: v3 = 0x81
+    0x833E, // 270: v3 <<= 1
+    0x8EF0, // 272: ve = vf
+    0x8030, // 274: v0 = v3, 0x02
+    0x228A, // 276: call show
+    0x80E0, // 278: v0 = ve, shifted out 1
+    0x228A, // 27A: call show
+    0x6FFF, // 27C: vf = 0xff
+    0x6302, // 27E: v3 = 0x02
+    0x8F34, // 280: vf += v3
+    0x8EF0, // 282: ve = vf
+    0x80E0, // 284: v0 = ve, carry 1
+    0x228A, // 286: call show
+    0x1288, // 288: loop forever
+    0x8100, // 28A: v1 = v0
+    0x8116, // 28C: v1 >>= 1
+    0x8116, // 28E: v1 >>= 1
+    0x8116, // 290: v1 >>= 1
+    0x8116, // 292: v1 >>= 1
+    0xF129, // 294: i = font(v1)
+    0xDCD5, // 296: draw the high digit
+    0x7C05, // 298: vc += 5
+    0x620F, // 29A: v2 = 0x0f
+    0x8022, // 29C: v0 &= v2
+    0xF029, // 29E: i = font(v0)
+    0xDCD5, // 2A0: draw the low digit
+    0x7C06, // 2A2: vc += 6
+    0x00EE, // 2A4: return
+];
+
+/// Shows one value per quirk, `01` or `02` for jump_vx, `05` or `00` for vf_reset, `AA` or
+/// `00` for memory_increment and `01` or `04` for shift_vy, then draws a sprite across the
+/// right edge that wraps around unless clip is set.
+const QUIRKS_PROGRAM: &[u16] = &[
+    0x6C00, // 200: vc = 0
+    0x6D00, // 202: vd = 0
+    0x6000, // 204: v0 = 0
+    0x6204, // 206: v2 = 4
+    0x6E00, // 208: ve = 0
+    0xB20C, // 20A: jump to jump + v0, or + v2 with jump_vx
+    0x6E01, // 20C: ve = 1, without jump_vx
+    0x1212, // 20E: skip the jump_vx target
+    0x6E02, // 210: ve = 2, with jump_vx
+    0x6F05, // 212: vf = 5
+    0x6301, // 214: v3 = 1
+    0x6402, // 216: v4 = 2
+    0x8341, // 218: v3 |= v4, resets vf with vf_reset
+    0x8BF0, // 21A: vb = vf
+    0x60AA, // 21C: v0 = 0xaa
+    0xA3F0, // 21E: i = 0x3f0
+    0xF055, // 220: store v0, i = 0x3f1 with memory_increment
+    0x6000, // 222: v0 = 0
+    0xF065, // 224: load v0 from i
+    0x8A00, // 226: va = v0
+    0x6302, // 228: v3 = 2
+    0x6408, // 22A: v4 = 8
+    0x8346, // 22C: v3 = v4 >> 1 with shift_vy, else v3 >> 1
+    0x80E0, // 22E: v0 = ve, 1 or 2 with jump_vx
+    0x224C, // 230: call show
+    0x80B0, // 232: v0 = vb, 5 or 0 with vf_reset
+    0x224C, // 234: call show
+    0x80A0, // 236: v0 = va, aa or 00 with memory_increment
+    0x224C, // 238: call show
+    0x8030, // 23A: v0 = v3, 01 or 04 with shift_vy
+    0x224C, // 23C: call show
+    0x6C00, // 23E: vc = 0
+    0x7D06, // 240: vd += 6
+    0xA268, // 242: i = sprite
+    0x633C, // 244: v3 = 60
+    0x6410, // 246: v4 = 16
+    0xD344, // 248: draw 8x4 at (60, 16)
+    0x124A, // 24A: loop forever
+    0x8100, // 24C: v1 = v0
+    0x8116, // 24E: v1 >>= 1
+    0x8116, // 250: v1 >>= 1
+    0x8116, // 252: v1 >>= 1
+    0x8116, // 254: v1 >>= 1
+    0xF129, // 256: i = font(v1)
+    0xDCD5, // 258: draw the high digit
+    0x7C05, // 25A: vc += 5
+    0x620F, // 25C: v2 = 0x0f
+    0x8022, // 25E: v0 &= v2
+    0xF029, // 260: i = font(v0)
+    0xDCD5, // 262: draw the low digit
+    0x7C06, // 264: vc += 6
+    0x00EE, // 266: return
+    0xFFFF, // 268: sprite rows 0-1
+    0xFFFF, // 26A: sprite rows 2-3
+];
+
+/// Waits for key 5 to be held with EX9E and released with EXA1, checks both fall through
+/// when not taken, then waits for a key with FX0A. Shows the key, `01` and `04`.
+const KEYPAD_PROGRAM: &[u16] = &[
+    0x6C00, // 200: vc = 0
+    0x6D00, // 202: vd = 0
+    0x6305, // 204: v3 = 5
+    0x6500, // 206: v5 = 0
+    0x6600, // 208: v6 = 0
+    0xE39E, // 20A: skip if key v3 is held
+    0x120A, // 20C: wait for key v3
+    0x7601, // 20E: v6 += 1
+    0xE3A1, // 210: skip if key v3 isn't held
+    0x1210, // 212: wait for the release
+    0x7601, // 214: v6 += 1
+    0xE39E, // 216: key v3 isn't held, no skip
+    0x7501, // 218: v5 += 1
+    0x7601, // 21A: v6 += 1
+    0xE3A1, // 21C: key v3 isn't held, skip
+    0x7510, // 21E: v5 += 0x10, skipped
+    0xF40A, // 220: wait for a key press and release
+    0x7601, // 222: v6 += 1
+    0x8040, // 224: v0 = v4, the key waited for
+    0x2232, // 226: call show
+    0x8050, // 228: v0 = v5, 01
+    0x2232, // 22A: call show
+    0x8060, // 22C: v0 = v6, 04
+    0x2232, // 22E: call show
+    0x1230, // 230: loop forever
+    0x8100, // 232: v1 = v0
+    0x8116, // 234: v1 >>= 1
+    0x8116, // 236: v1 >>= 1
+    0x8116, // 238: v1 >>= 1
+    0x8116, // 23A: v1 >>= 1
+    0xF129, // 23C: i = font(v1)
+    0xDCD5, // 23E: draw the high digit
+    0x7C05, // 240: vc += 5
+    0x620F, // 242: v2 = 0x0f
+    0x8022, // 244: v0 &= v2
+    0xF029, // 246: i = font(v0)
+    0xDCD5, // 248: draw the low digit
+    0x7C06, // 24A: vc += 6
+    0x00EE, // 24C: return
+];
+
+/// Runs the skips, 7XNN, calls, FX33, ANNN and FX1E. Shows `0F 02 00 11` then `01 05 06 AB`.
+const OPCODES_PROGRAM: &[u16] = &[
+    0x6C00, // 200: vc = 0
+    0x6D00, // 202: vd = 0
+    0x6500, // 204: v5 = 0
+    0x6307, // 206: v3 = 7
+    0x6407, // 208: v4 = 7
+    0x6608, // 20A: v6 = 8
+    0x3307, // 20C: skip if v3 == 7
+    0x7580, // 20E: v5 += 0x80, skipped
+    0x3308, // 210: skip if v3 == 8
+    0x7501, // 212: v5 += 0x01
+    0x4308, // 214: skip if v3 != 8
+    0x7540, // 216: v5 += 0x40, skipped
+    0x4307, // 218: skip if v3 != 7
+    0x7502, // 21A: v5 += 0x02
+    0x5340, // 21C: skip if v3 == v4
+    0x7520, // 21E: v5 += 0x20, skipped
+    0x5360, // 220: skip if v3 == v6
+    0x7504, // 222: v5 += 0x04
+    0x9360, // 224: skip if v3 != v6
+    0x7510, // 226: v5 += 0x10, skipped
+    0x9340, // 228: skip if v3 != v4
+    0x7508, // 22A: v5 += 0x08
+    0x6F00, // 22C: vf = 0
+    0x67FF, // 22E: v7 = 0xff
+    0x7703, // 230: v7 += 3
+    0x8EF0, // 232: ve = vf
+    0x6800, // 234: v8 = 0
+    0x229C, // 236: call sub
+    0x7810, // 238: v8 += 0x10
+    0x699C, // 23A: v9 = 156
+    0xA3F0, // 23C: i = 0x3f0
+    0xF933, // 23E: bcd of v9
+    0xF265, // 240: load v0-v2
+    0x8A00, // 242: va = v0
+    0x8910, // 244: v9 = v1
+    0x8320, // 246: v3 = v2
+    0xA100, // 248: i = 0x100
+    0x64F0, // 24A: v4 = 0xf0
+    0xF41E, // 24C: i += v4
+    0x60AB, // 24E: v0 = 0xab
+    0xF055, // 250: store v0 at 0x1f0
+    0xA1F0, // 252: i = 0x1f0
+    0x6000, // 254: v0 = 0
+    0xF065, // 256: load v0
+    0x8B00, // 258: vb = v0
+    0x8050, // 25A: v0 = v5, 0f
+    0x2280, // 25C: call show
+    0x8070, // 25E: v0 = v7, 02
+    0x2280, // 260: call show
+    0x80E0, // 262: v0 = ve, 00
+    0x2280, // 264: call show
+    0x8080, // 266: v0 = v8, 11
+    0x2280, // 268: call show
+    0x6C00, // 26A: vc = 0
+    0x7D06, // 26C: vd += 6
+    0x80A0, // 26E: v0 = va, 01
+    0x2280, // 270: call show
+    0x8090, // 272: v0 = v9, 05
+    0x2280, // 274: call show
+    0x8030, // 276: v0 = v3, 06
+    0x2280, // 278: call show
+    0x80B0, // 27A: v0 = vb, ab
+    0x2280, // 27C: call show
+    0x127E, // 27E: loop forever
+    0x8100, // 280: v1 = v0
+    0x8116, // 282: v1 >>= 1
+    0x8116, // 284: v1 >>= 1
+    0x8116, // 286: v1 >>= 1
+    0x8116, // 288: v1 >>= 1
+    0xF129, // 28A: i = font(v1)
+    0xDCD5, // 28C: draw the high digit
+    0x7C05, // 28E: vc += 5
+    0x620F, // 290: v2 = 0x0f
+    0x8022, // 292: v0 &= v2
+    0xF029, // 294: i = font(v0)
+    0xDCD5, // 296: draw the low digit
+    0x7C06, // 298: vc += 6
+    0x00EE, // 29A: return
+    0x7801, // 29C: v8 += 1
+    0x00EE, // 29E: return
+];
+
+enum Rom {
+    /// Path under `rom/`
+    File(&'static str),
+    /// Opcodes loaded at the program start address
+    Program(&'static [u16]),
+}
+
+struct TestCase {
+    name: &'static str,
+    rom: Rom,
+    variant: Variant,
+    quirks: Quirks,
+    /// Byte preset at `PLATFORM_ADDRESS`
+    platform: Option<Word>,
+    inputs: Vec<(u64, Key, InputKind)>,
+    num_cycles: u64,
+}
+
+impl TestCase {
+    fn new(name: &'static str, rom: &'static str) -> Self {
+        Self::with_rom(name, Rom::File(rom))
+    }
+
+    fn program(name: &'static str, program: &'static [u16]) -> Self {
+        Self::with_rom(name, Rom::Program(program))
+    }
+
+    fn with_rom(name: &'static str, rom: Rom) -> Self {
+        Self {
+            name,
+            rom,
+            variant: Variant::Chip8,
+            quirks: Quirks::default(),
+            platform: None,
+            inputs: Vec::new(),
+            num_cycles: 100_000,
+        }
+    }
+
+    fn with_variant(mut self, variant: Variant, quirks: Quirks) -> Self {
+        self.variant = variant;
+        self.quirks = quirks;
+        self
+    }
+
+    fn with_platform(mut self, platform: Word) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    fn with_input(mut self, clk: u64, key: Key, kind: InputKind) -> Self {
+        self.inputs.push((clk, key, kind));
+        self
+    }
+
+    fn with_num_cycles(mut self, num_cycles: u64) -> Self {
+        self.num_cycles = num_cycles;
+        self
+    }
+
+    /// Runs the ROM for the cycle budget.
+    fn run(&self) -> FrameBuffer {
+        let rom = match self.rom {
+            Rom::File(file) => {
+                let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("../rom")
+                    .join(file);
+                fs::read(&path).unwrap_or_else(|_| {
+                    panic!(
+                        "{}: missing {}, run git submodule update --init",
+                        self.name,
+                        path.display()
+                    )
+                })
+            }
+            Rom::Program(program) => program.iter().flat_map(|op| op.to_be_bytes()).collect(),
+        };
+
+        let mut cpu = SimpleCpu::new(CLK_FREQ, Lfsr::new(RANDOM_SEED))
+            .with_quirks(self.quirks)
+            .with_variant(self.variant);
+        cpu.state().load_rom(&rom).unwrap();
+        if let Some(platform) = self.platform {
+            cpu.state().set_memory(PLATFORM_ADDRESS, platform).unwrap();
+        }
+
+        let mut inputs: VecDeque<(u64, InputEvent)> = self
+            .inputs
+            .iter()
+            .map(|&(clk, key, kind)| (clk, InputEvent { key, kind }))
+            .collect();
+        for clk in 0..self.num_cycles {
+            match cpu.step(&mut inputs) {
+                Ok(()) => {}
+                Err(Chip8Error::Terminated) => break,
+                Err(e) => panic!("{}: {e} at clk {clk}", self.name),
+            }
+        }
+
+        let frame_buffer = cpu.state().frame_buffer_ptr();
+        let frame_buffer = frame_buffer.read().unwrap();
+        frame_buffer.clone()
+    }
+
+    fn check(&self) {
+        let actual = render(&self.run());
+
+        let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.txt", self.name));
+        if env::var_os("CHIP8_BLESS").is_some() {
+            fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            fs::write(&golden, &actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+            panic!(
+                "{}: missing {}, run with CHIP8_BLESS=1 to create it",
+                self.name,
+                golden.display()
+            )
+        });
+        assert!(
+            actual == expected,
+            "{}: frame buffer differs from {}\nexpected:\n{expected}\nactual:\n{actual}",
+            self.name,
+            golden.display()
+        );
+    }
+}
+
+fn render(frame_buffer: &FrameBuffer) -> String {
+    frame_buffer
+        .rows()
+        .map(|row| {
+            let mut line: String = row.iter().map(|&color| PALETTE[color as usize]).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn chip8_logo() {
+    TestCase::new("chip8_logo", "chip8-test-suite/bin/1-chip8-logo.ch8").check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn ibm_logo() {
+    TestCase::new("ibm_logo", "chip8-test-suite/bin/2-ibm-logo.ch8").check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn corax_plus() {
+    TestCase::new("corax_plus", "chip8-test-suite/bin/3-corax+.ch8").check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn corax_test_opcode() {
+    TestCase::new("corax_test_opcode", "chip8-test-rom/test_opcode.ch8").check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn flags() {
+    TestCase::new("flags", "chip8-test-suite/bin/4-flags.ch8").check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn quirks_chip8() {
+    TestCase::new("quirks_chip8", "chip8-test-suite/bin/5-quirks.ch8")
+        .with_variant(Variant::Chip8, Quirks::VIP)
+        .with_platform(1)
+        .with_num_cycles(1_000_000)
+        .check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn quirks_schip() {
+    TestCase::new("quirks_schip", "chip8-test-suite/bin/5-quirks.ch8")
+        .with_variant(Variant::SuperChip, Quirks::SCHIP)
+        .with_platform(2)
+        .with_num_cycles(1_000_000)
+        .check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn quirks_xochip() {
+    TestCase::new("quirks_xochip", "chip8-test-suite/bin/5-quirks.ch8")
+        .with_variant(Variant::XoChip, Quirks::XO_CHIP)
+        .with_platform(3)
+        .with_num_cycles(1_000_000)
+        .check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn keypad_pressed() {
+    // EX9E lights up the held keys
+    TestCase::new("keypad_pressed", "chip8-test-suite/bin/6-keypad.ch8")
+        .with_platform(1)
+        .with_input(10_000, Key::Key5, InputKind::Press)
+        .check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn keypad_released() {
+    // EXA1 lights up the keys that aren't held
+    TestCase::new("keypad_released", "chip8-test-suite/bin/6-keypad.ch8")
+        .with_platform(2)
+        .with_input(10_000, Key::Key5, InputKind::Press)
+        .check();
+}
+
+#[test]
+#[ignore = "needs the rom/ submodules, which aren't pinned yet"]
+fn keypad_wait() {
+    // FX0A only completes once the key is released
+    TestCase::new("keypad_wait", "chip8-test-suite/bin/6-keypad.ch8")
+        .with_platform(3)
+        .with_input(10_000, Key::Key5, InputKind::Press)
+        .with_input(20_000, Key::Key5, InputKind::Release)
+        .check();
+}
+
+#[test]
+fn flags_program() {
+    TestCase::program("flags_program", FLAGS_PROGRAM).check();
+}
+
+#[test]
+fn quirks_program_chip8() {
+    TestCase::program("quirks_program_chip8", QUIRKS_PROGRAM)
+        .with_variant(Variant::Chip8, Quirks::VIP)
+        .check();
+}
+
+#[test]
+fn quirks_program_schip() {
+    TestCase::program("quirks_program_schip", QUIRKS_PROGRAM)
+        .with_variant(Variant::SuperChip, Quirks::SCHIP)
+        .check();
+}
+
+#[test]
+fn quirks_program_xochip() {
+    TestCase::program("quirks_program_xochip", QUIRKS_PROGRAM)
+        .with_variant(Variant::XoChip, Quirks::XO_CHIP)
+        .check();
+}
+
+#[test]
+fn keypad_program() {
+    TestCase::program("keypad_program", KEYPAD_PROGRAM)
+        .with_input(1_000, Key::Key5, InputKind::Press)
+        .with_input(2_000, Key::Key5, InputKind::Release)
+        .with_input(3_000, Key::KeyA, InputKind::Press)
+        .with_input(4_000, Key::KeyA, InputKind::Release)
+        .check();
+}
+
+#[test]
+fn opcodes_program() {
+    TestCase::program("opcodes_program", OPCODES_PROGRAM).check();
+}